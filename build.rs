@@ -10,7 +10,10 @@ use fs_extra::dir::{copy, CopyOptions};
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=Cargo.lock");
-    println!("cargo:rustc-link-arg-bins=/STACK:100000000");
+    // The board lives on the stack, which is only 1MB by default with MSVC. Other linkers don't understand the flag
+    if env::var("CARGO_CFG_TARGET_ENV").is_ok_and(|env| env == "msvc") {
+        println!("cargo:rustc-link-arg-bins=/STACK:100000000");
+    }
     let binding = PathBuf::from(env::var_os("OUT_DIR").unwrap());
     let target_dir = binding.parent().unwrap().parent().unwrap().parent().unwrap();
     let mut options = CopyOptions::new();
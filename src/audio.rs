@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::path::Path;
+use sdl2::{AudioSubsystem, Sdl};
+use sdl2::mixer::{AUDIO_S16LSB, Channel, Chunk, DEFAULT_CHANNELS, InitFlag, MAX_VOLUME, Music, Sdl2MixerContext};
+use crate::col_println;
+use crate::game::GameEvent;
+
+const VOLUME_STEP: i32 = MAX_VOLUME / 8;
+const MUSIC_FADE_MS: i32 = 500;
+/// About as long as `MUSIC_FADE_MS` at 60 frames a second
+const LAYER_FADE_FRAMES: f32 = 30.0;
+const HEARTBEAT_START: f32 = 0.6;
+const HEARTBEAT_FRAMES: (f32, f32) = (60.0, 15.0);
+/// Small clears and short combos play at this fraction of the volume, rising to full at the sizes below
+const QUIETEST_EFFECT: f32 = 0.5;
+const LOUDEST_CLEAR_PIXELS: usize = 20_000;
+const LOUDEST_COMBO: u32 = 5;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    Clear,
+    Combo,
+    GameOver,
+    Heartbeat,
+}
+
+impl Sound {
+    const ALL: [Sound; 7] = [Sound::Move, Sound::Rotate, Sound::Lock, Sound::Clear, Sound::Combo, Sound::GameOver, Sound::Heartbeat];
+
+    fn file_name(&self) -> &'static str {
+        match self {
+            Sound::Move => "move.wav",
+            Sound::Rotate => "rotate.wav",
+            Sound::Lock => "lock.wav",
+            Sound::Clear => "clear.wav",
+            Sound::Combo => "combo.wav",
+            Sound::GameOver => "game_over.wav",
+            Sound::Heartbeat => "heartbeat.wav",
+        }
+    }
+
+    /// Notes of the effect used when there is no file for it, as `(start hz, end hz, milliseconds)`
+    fn notes(&self) -> &'static [(f32, f32, u32)] {
+        match self {
+            Sound::Move => &[(440.0, 440.0, 30)],
+            Sound::Rotate => &[(660.0, 700.0, 40)],
+            Sound::Lock => &[(220.0, 160.0, 80)],
+            Sound::Clear => &[(523.0, 1046.0, 250)],
+            Sound::Combo => &[(880.0, 880.0, 80), (1320.0, 1320.0, 120)],
+            Sound::GameOver => &[(440.0, 330.0, 250), (330.0, 220.0, 250), (220.0, 110.0, 500)],
+            Sound::Heartbeat => &[(70.0, 50.0, 90), (0.0, 0.0, 60), (70.0, 50.0, 90)],
+        }
+    }
+}
+
+/// How close the stack is to the top, each with its own music stem.
+///
+/// Only the calm track, `static/Tetris.mp3`, is shipped. To add the warning and critical stems, put
+/// `static/Tetris_warning.mp3` and `static/Tetris_critical.mp3` next to it. Each loops and fades in from its start
+/// when the danger level changes. While a stem is missing, a synthesised loop is layered over the track below it
+/// instead, a bass pulse for warning and a fast tick for critical, fading in and out with the danger level
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+enum DangerLevel {
+    Calm,
+    Warning,
+    Critical,
+}
+
+impl DangerLevel {
+    const ALL: [DangerLevel; 3] = [DangerLevel::Calm, DangerLevel::Warning, DangerLevel::Critical];
+
+    fn from_danger(danger: f32) -> Self {
+        if danger >= 0.85 { DangerLevel::Critical }
+        else if danger >= 0.6 { DangerLevel::Warning }
+        else { DangerLevel::Calm }
+    }
+
+    fn music_file(&self) -> &'static str {
+        match self {
+            DangerLevel::Calm => "static/Tetris.mp3",
+            DangerLevel::Warning => "static/Tetris_warning.mp3",
+            DangerLevel::Critical => "static/Tetris_critical.mp3",
+        }
+    }
+
+    /// Notes of the layer looped in place of a missing stem, in the same form as `Sound::notes`
+    fn layer_notes(&self) -> &'static [(f32, f32, u32)] {
+        match self {
+            DangerLevel::Calm => &[],
+            DangerLevel::Warning => &[(82.0, 78.0, 180), (0.0, 0.0, 70), (82.0, 78.0, 120), (0.0, 0.0, 130)],
+            DangerLevel::Critical => &[(3_000.0, 2_500.0, 25), (0.0, 0.0, 100)],
+        }
+    }
+}
+
+/// A looping layer played over the music on its own reserved channel
+struct Layer {
+    danger_level: DangerLevel,
+    channel: Channel,
+    /// Kept alive for as long as the channel plays it
+    _chunk: Chunk,
+    fade: f32,
+}
+
+/// Background music and sound effects. If no audio device can be opened everything is silently skipped
+pub struct Audio {
+    _audio_subsystem: Option<AudioSubsystem>,
+    _mixer_context: Option<Sdl2MixerContext>,
+    tracks: HashMap<DangerLevel, Music<'static>>,
+    sounds: HashMap<Sound, Chunk>,
+    layers: Vec<Layer>,
+    volume: i32,
+    muted: bool,
+    danger_level: DangerLevel,
+    frames_since_heartbeat: u32,
+}
+
+impl Audio {
+    pub fn new(sdl_context: &Sdl) -> Self {
+        let mut audio = Self {
+            _audio_subsystem: None,
+            _mixer_context: None,
+            tracks: HashMap::new(),
+            sounds: HashMap::new(),
+            layers: Vec::new(),
+            volume: MAX_VOLUME / 2,
+            muted: false,
+            danger_level: DangerLevel::Calm,
+            frames_since_heartbeat: 0
+        };
+
+        if let Err(e) = audio.open(sdl_context) {
+            col_println!((yellow, bold), "Audio unavailable, continuing without sound: {}", e);
+        }
+        audio
+    }
+
+    fn open(&mut self, sdl_context: &Sdl) -> Result<(), String> {
+        self._audio_subsystem = Some(sdl_context.audio()?);
+        sdl2::mixer::open_audio(44_100, AUDIO_S16LSB, DEFAULT_CHANNELS, 1_024)?;
+        // MP3 support is only needed for the music, effects still work without it
+        self._mixer_context = sdl2::mixer::init(InitFlag::MP3).ok();
+        sdl2::mixer::allocate_channels(16);
+
+        let (frequency, _, channels) = sdl2::mixer::query_spec()?;
+        for sound in Sound::ALL {
+            let path = Path::new("static/sfx").join(sound.file_name());
+            let chunk = match Chunk::from_file(&path) {
+                Ok(chunk) => chunk,
+                Err(_) => Chunk::from_raw_buffer(synthesise(sound.notes(), frequency, channels))?
+            };
+            self.sounds.insert(sound, chunk);
+        }
+
+        // Only the calm track is required, the others are optional stems
+        for danger_level in DangerLevel::ALL {
+            match Music::from_file(danger_level.music_file()) {
+                Ok(music) => { self.tracks.insert(danger_level, music); }
+                Err(e) if danger_level == DangerLevel::Calm => col_println!((yellow, bold), "Failed to load music: {}", e),
+                Err(_) => {}
+            }
+        }
+        if let Some(music) = self.tracks.get(&DangerLevel::Calm) {
+            music.play(-1)?;
+        }
+
+        // Layers for the missing stems play silently on the reserved channels until they're needed
+        let layer_levels: Vec<_> = [DangerLevel::Warning, DangerLevel::Critical].into_iter()
+            .filter(|danger_level| !self.tracks.contains_key(danger_level))
+            .collect();
+        sdl2::mixer::reserve_channels(layer_levels.len() as i32);
+        for (index, danger_level) in layer_levels.into_iter().enumerate() {
+            let chunk = Chunk::from_raw_buffer(synthesise(danger_level.layer_notes(), frequency, channels))?;
+            Channel(index as i32).set_volume(0);
+            let channel = Channel(index as i32).play(&chunk, -1)?;
+            self.layers.push(Layer { danger_level, channel, _chunk: chunk, fade: 0.0 });
+        }
+
+        self.apply_volume();
+        Ok(())
+    }
+
+    fn apply_volume(&self) {
+        let volume = if self.muted { 0 } else { self.volume };
+        Music::set_volume(volume);
+        Channel::all().set_volume(volume);
+        self.apply_layer_volumes();
+    }
+
+    fn apply_layer_volumes(&self) {
+        let volume = if self.muted { 0 } else { self.volume };
+        for layer in &self.layers {
+            layer.channel.set_volume((volume as f32 * layer.fade) as i32);
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.apply_volume();
+    }
+
+    pub fn volume_up(&mut self) {
+        self.volume = (self.volume + VOLUME_STEP).min(MAX_VOLUME);
+        self.apply_volume();
+    }
+
+    pub fn volume_down(&mut self) {
+        self.volume = (self.volume - VOLUME_STEP).max(0);
+        self.apply_volume();
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Volume from 0 to 100
+    pub fn volume_percent(&self) -> i32 {
+        self.volume * 100 / MAX_VOLUME
+    }
+
+    pub fn pause_music(&self) {
+        for layer in &self.layers {
+            layer.channel.pause();
+        }
+        if self.tracks.is_empty() { return; }
+        Music::pause();
+    }
+
+    pub fn resume_music(&self) {
+        for layer in &self.layers {
+            layer.channel.resume();
+        }
+        if self.tracks.is_empty() { return; }
+        Music::resume();
+    }
+
+    /// Starts the calm track again from the beginning, for a new game
+    pub fn restart_music(&mut self) {
+        self.danger_level = DangerLevel::Calm;
+        self.frames_since_heartbeat = 0;
+        for layer in &mut self.layers {
+            layer.fade = 0.0;
+            layer.channel.resume();
+        }
+        self.apply_layer_volumes();
+        if let Some(music) = self.tracks.get(&DangerLevel::Calm) {
+            let _ = music.play(-1);
+            Music::resume();
+        }
+    }
+
+    pub fn play(&self, sound: Sound) {
+        self.play_at(sound, 1.0);
+    }
+
+    /// Plays `sound` at a fraction of the effects volume
+    fn play_at(&self, sound: Sound, loudness: f32) {
+        if self.muted { return; }
+        if let Some(chunk) = self.sounds.get(&sound) {
+            // Running out of free channels just drops the effect
+            if let Ok(channel) = Channel::all().play(chunk, 0) {
+                channel.set_volume((self.volume as f32 * loudness) as i32);
+            }
+        }
+    }
+
+    /// Track actually played for `danger_level`, falling back to the highest stem below it when one is missing
+    fn track_level(&self, danger_level: DangerLevel) -> DangerLevel {
+        DangerLevel::ALL.into_iter().rev()
+            .find(|level| *level <= danger_level && self.tracks.contains_key(level))
+            .unwrap_or(DangerLevel::Calm)
+    }
+
+    /// Switches music stem or fades the layers standing in for missing stems as the stack rises, and plays a
+    /// quickening heartbeat when it gets close to the top. Call once per frame with `Game::danger`
+    pub fn set_danger(&mut self, danger: f32) {
+        let danger_level = DangerLevel::from_danger(danger);
+        if danger_level != self.danger_level {
+            let previous_track = self.track_level(self.danger_level);
+            let track = self.track_level(danger_level);
+            self.danger_level = danger_level;
+
+            if track != previous_track {
+                if let Some(music) = self.tracks.get(&track) {
+                    let _ = music.fade_in(-1, MUSIC_FADE_MS);
+                }
+            }
+        }
+
+        let track = self.track_level(self.danger_level);
+        for layer in &mut self.layers {
+            let target = if layer.danger_level <= self.danger_level && track < layer.danger_level { 1.0 } else { 0.0 };
+            layer.fade = if target > layer.fade {
+                (layer.fade + 1.0 / LAYER_FADE_FRAMES).min(target)
+            } else {
+                (layer.fade - 1.0 / LAYER_FADE_FRAMES).max(target)
+            };
+        }
+        self.apply_layer_volumes();
+
+        if danger < HEARTBEAT_START {
+            self.frames_since_heartbeat = 0;
+            return;
+        }
+
+        let progress = ((danger - HEARTBEAT_START) / (1.0 - HEARTBEAT_START)).min(1.0);
+        let interval = HEARTBEAT_FRAMES.0 + (HEARTBEAT_FRAMES.1 - HEARTBEAT_FRAMES.0) * progress;
+        self.frames_since_heartbeat += 1;
+        if self.frames_since_heartbeat as f32 >= interval {
+            self.frames_since_heartbeat = 0;
+            self.play(Sound::Heartbeat);
+        }
+    }
+
+    pub fn handle_event(&self, event: &GameEvent) {
+        match event {
+            GameEvent::Move => self.play(Sound::Move),
+            GameEvent::Rotate => self.play(Sound::Rotate),
+            GameEvent::Lock => self.play(Sound::Lock),
+            GameEvent::Clear { pixels } => self.play_at(Sound::Clear, loudness(*pixels as f32 / LOUDEST_CLEAR_PIXELS as f32)),
+            GameEvent::Combo(chain) => self.play_at(Sound::Combo, loudness(*chain as f32 / LOUDEST_COMBO as f32)),
+            GameEvent::GameOver => self.play(Sound::GameOver),
+            GameEvent::Overflow => self.play(Sound::Clear),
+            GameEvent::Won => self.play(Sound::Combo),
+        }
+    }
+}
+
+/// Volume of an effect `size` of the way to its loudest
+fn loudness(size: f32) -> f32 {
+    QUIETEST_EFFECT + (1.0 - QUIETEST_EFFECT) * size.min(1.0)
+}
+
+/// Builds a decaying sine sweep for each note, one after another, as interleaved signed 16 bit samples
+fn synthesise(notes: &[(f32, f32, u32)], frequency: i32, channels: i32) -> Box<[i16]> {
+    const AMPLITUDE: f32 = 0.25 * i16::MAX as f32;
+    let mut samples = Vec::new();
+
+    for &(start_hz, end_hz, milliseconds) in notes {
+        let length = (frequency as u32 * milliseconds / 1000) as usize;
+        let mut phase = 0.0f32;
+
+        for i in 0..length {
+            let progress = i as f32 / length as f32;
+            phase += (start_hz + (end_hz - start_hz) * progress) / frequency as f32;
+            let sample = ((phase * TAU).sin() * AMPLITUDE * (1.0 - progress)) as i16;
+            for _ in 0..channels {
+                samples.push(sample);
+            }
+        }
+    }
+
+    samples.into_boxed_slice()
+}
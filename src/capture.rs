@@ -0,0 +1,123 @@
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+use png::{BitDepth, ColorType, Encoder};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::col_println;
+use crate::config::data_dir;
+
+/// Only every nth frame is recorded, giving 30 fps clips at 60 fps
+const RECORD_EVERY: u64 = 2;
+/// Frames waiting to be encoded before new ones are dropped, so a slow disk can't use up memory
+const RECORD_QUEUE: usize = 60;
+
+fn timestamp() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis())
+}
+
+fn capture_dir(name: &str) -> Result<PathBuf, String> {
+    let directory = data_dir().ok_or("No data directory")?.join(name);
+    fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+    Ok(directory)
+}
+
+/// Writes tightly packed RGB24 pixels as a PNG
+pub fn write_png(path: &Path, size: (u32, u32), pixels: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = Encoder::new(BufWriter::new(file), size.0, size.1);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())
+}
+
+/// Saves everything drawn to the canvas so far this frame, so call it before presenting
+pub fn screenshot(canvas: &Canvas<Window>) -> Result<PathBuf, String> {
+    let size = canvas.output_size()?;
+    let pixels = canvas.read_pixels(None, PixelFormatEnum::RGB24)?;
+    let path = capture_dir("screenshots")?.join(format!("screenshot-{}.png", timestamp()));
+    write_png(&path, size, &pixels)?;
+    Ok(path)
+}
+
+struct Recording {
+    directory: PathBuf,
+    sender: SyncSender<(PathBuf, Vec<u8>)>,
+    writer: JoinHandle<()>,
+    frames: u64,
+    frames_seen: u64,
+    frames_dropped: u64,
+}
+
+/// Records board frames as a numbered PNG sequence, encoding on a separate thread so play doesn't stutter
+pub struct Recorder {
+    size: (u32, u32),
+    recording: Option<Recording>,
+}
+
+impl Recorder {
+    /// `size` is the size in pixels of every frame passed to `record`
+    pub fn new(size: (u32, u32)) -> Self {
+        Self { size, recording: None }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn toggle(&mut self) -> Result<(), String> {
+        match self.recording.take() {
+            Some(recording) => {
+                // Closing the channel lets the writer finish the frames it has and exit
+                drop(recording.sender);
+                recording.writer.join().map_err(|_| "Recording thread panicked")?;
+                col_println!((green, bold), "Saved {} frames to {}", recording.frames, recording.directory.display());
+                if recording.frames_dropped > 0 {
+                    col_println!((yellow, bold), "Dropped {} frames that couldn't be encoded in time", recording.frames_dropped);
+                }
+            }
+            None => {
+                let directory = capture_dir("recordings")?.join(format!("recording-{}", timestamp()));
+                fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+
+                let (sender, receiver) = sync_channel::<(PathBuf, Vec<u8>)>(RECORD_QUEUE);
+                let size = self.size;
+                let writer = std::thread::spawn(move || {
+                    for (path, pixels) in receiver {
+                        if let Err(e) = write_png(&path, size, &pixels) {
+                            col_println!((red, bold), "Failed to write frame {}: {}", path.display(), e);
+                        }
+                    }
+                });
+
+                col_println!((green, bold), "Recording to {}", directory.display());
+                self.recording = Some(Recording { directory, sender, writer, frames: 0, frames_seen: 0, frames_dropped: 0 });
+            }
+        }
+        Ok(())
+    }
+
+    /// Call every frame with the RGB24 pixels of the frame
+    pub fn record(&mut self, pixels: &[u8]) {
+        let recording = match &mut self.recording {
+            Some(recording) => recording,
+            None => return
+        };
+
+        recording.frames_seen += 1;
+        if recording.frames_seen % RECORD_EVERY != 0 { return; }
+
+        let path = recording.directory.join(format!("frame-{:05}.png", recording.frames));
+        match recording.sender.try_send((path, pixels.to_vec())) {
+            Ok(()) => recording.frames += 1,
+            // The writer is behind, so skip this frame rather than stall the game
+            Err(_) => recording.frames_dropped += 1
+        }
+    }
+}
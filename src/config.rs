@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::col_println;
+use crate::game::{DEFAULT_COLOURS, MIN_COLOURS};
+use crate::game::clear::ClearRule;
+use crate::game::mode::GameMode;
+use crate::game::palette::{MAX_COLOURS, Palette};
+
+/// Per-user directory for settings and saved data, if the platform has one
+pub fn data_dir() -> Option<PathBuf> {
+    sdl2::filesystem::pref_path("RobertLucas", "PhysicsTetris").ok().map(PathBuf::from)
+}
+
+/// Settings read from `config.txt` in the data directory as `key = value` lines.
+/// Custom palettes are given as `palette.<name> = r,g,b r,g,b ...`, and clear rules for one mode as `clear_rule.<mode> = <rule>`
+pub struct Config {
+    pub palette: String,
+    pub patterns: bool,
+    pub custom_palettes: Vec<Palette>,
+    /// Number of colours pieces spawn in
+    pub colours: usize,
+    pub screen_shake: bool,
+    pub assist: bool,
+    /// Rule for modes without their own. See `ClearRule::parse` for the format
+    pub clear_rule: ClearRule,
+    pub mode_clear_rules: Vec<(GameMode, ClearRule)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            palette: "classic".to_string(),
+            patterns: false,
+            custom_palettes: Vec::new(),
+            colours: DEFAULT_COLOURS,
+            screen_shake: true,
+            assist: false,
+            clear_rule: ClearRule::default(),
+            mode_clear_rules: Vec::new()
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("config.txt"))
+    }
+
+    /// Loads the config, using defaults for anything missing or invalid
+    pub fn load() -> Self {
+        let mut config = Self::default();
+        let source = match Self::path().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(source) => source,
+            None => return config
+        };
+
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    col_println!((yellow, bold), "Ignoring invalid config line '{}'", line);
+                    continue;
+                }
+            };
+
+            if let Err(e) = config.set(key, value) {
+                col_println!((yellow, bold), "Ignoring config '{}': {}", key, e);
+            }
+        }
+
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if let Some(name) = key.strip_prefix("palette.") {
+            self.custom_palettes.push(Palette::parse(name, value)?);
+            return Ok(());
+        }
+        if let Some(name) = key.strip_prefix("clear_rule.") {
+            let mode = GameMode::from_name(name).ok_or(format!("Unknown mode '{}'", name))?;
+            self.mode_clear_rules.push((mode, ClearRule::parse(value)?));
+            return Ok(());
+        }
+
+        match key {
+            "palette" => self.palette = value.to_string(),
+            "patterns" => self.patterns = value.parse().map_err(|_| format!("Expected true or false, found '{}'", value))?,
+            "screen_shake" => self.screen_shake = value.parse().map_err(|_| format!("Expected true or false, found '{}'", value))?,
+            "assist" => self.assist = value.parse().map_err(|_| format!("Expected true or false, found '{}'", value))?,
+            "clear_rule" => self.clear_rule = ClearRule::parse(value)?,
+            "colours" => {
+                let colours = value.parse::<usize>().map_err(|_| format!("Expected a number, found '{}'", value))?;
+                if !(MIN_COLOURS..=MAX_COLOURS).contains(&colours) {
+                    return Err(format!("Expected between {} and {} colours", MIN_COLOURS, MAX_COLOURS));
+                }
+                self.colours = colours;
+            }
+            _ => return Err("Unknown key".to_string())
+        }
+        Ok(())
+    }
+
+    /// What counts as a clear in games of `mode`
+    pub fn clear_rule(&self, mode: GameMode) -> ClearRule {
+        self.mode_clear_rules.iter().rev().find(|(m, _)| *m == mode).map_or(self.clear_rule, |(_, rule)| *rule)
+    }
+
+    /// The selected palette, preferring custom palettes over built-in ones of the same name
+    pub fn palette(&self) -> Palette {
+        let mut palette = self.custom_palettes.iter().find(|p| p.name == self.palette).cloned()
+            .or_else(|| Palette::built_in(&self.palette))
+            .unwrap_or_else(|| {
+                col_println!((yellow, bold), "Unknown palette '{}', using classic", self.palette);
+                Palette::built_in("classic").unwrap()
+            });
+        palette.patterns = self.patterns;
+        palette
+    }
+}
@@ -0,0 +1,109 @@
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::display::Display;
+use crate::game::Game;
+use crate::text::Font;
+
+/// Physics updates run for every game update
+pub const PHYSICS_TICKS_PER_FRAME: u32 = 2;
+/// The simulation runs once every this many frames at each speed
+const SLOWDOWNS: [u64; 5] = [1, 2, 4, 8, 16];
+const OVERLAY_MARGIN: i32 = 20;
+const OVERLAY_SCALE: u32 = 2;
+const OVERLAY_COLOUR: Color = Color::RGB(255, 220, 0);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Step {
+    /// One `physics_update`
+    Physics,
+    /// One `game_update` and the physics updates that follow it
+    Game,
+}
+
+/// Keys for stepping through the simulation and an overlay describing the cell under the mouse, enabled with `--debug`:
+///
+/// - F7 freezes and unfreezes the simulation
+/// - F8 advances one physics tick while frozen, or one game tick with shift held
+/// - Page down and page up slow down and speed up the simulation
+pub struct DebugControls {
+    frozen: bool,
+    step: Option<Step>,
+    slowdown: usize,
+    frames: u64,
+    /// Mouse position in logical coordinates
+    mouse: Option<(i32, i32)>,
+}
+
+impl DebugControls {
+    pub fn new() -> Self {
+        Self {
+            frozen: false,
+            step: None,
+            slowdown: 0,
+            frames: 0,
+            mouse: None
+        }
+    }
+
+    /// Returns true if the event was used as a debug control. Mouse movement is tracked but never used up
+    pub fn handle_event(&mut self, event: &Event, display: &Display) -> bool {
+        match event {
+            Event::MouseMotion { x, y, .. } => {
+                self.mouse = Some(display.to_logical((*x, *y)));
+                false
+            }
+            Event::KeyDown { keycode: Some(Keycode::F7), .. } => {
+                self.frozen = !self.frozen;
+                true
+            }
+            Event::KeyDown { keycode: Some(Keycode::F8), keymod, .. } => {
+                if self.frozen {
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    self.step = Some(if shift { Step::Game } else { Step::Physics });
+                }
+                true
+            }
+            Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => {
+                self.slowdown = (self.slowdown + 1).min(SLOWDOWNS.len() - 1);
+                true
+            }
+            Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => {
+                self.slowdown = self.slowdown.saturating_sub(1);
+                true
+            }
+            _ => false
+        }
+    }
+
+    /// How much of the simulation to run this frame, as whether to run a game update and how many physics updates
+    pub fn tick(&mut self) -> (bool, u32) {
+        if self.frozen {
+            return match self.step.take() {
+                Some(Step::Physics) => (false, 1),
+                Some(Step::Game) => (true, PHYSICS_TICKS_PER_FRAME),
+                None => (false, 0)
+            };
+        }
+
+        self.frames += 1;
+        if self.frames.is_multiple_of(SLOWDOWNS[self.slowdown]) { (true, PHYSICS_TICKS_PER_FRAME) } else { (false, 0) }
+    }
+
+    /// Draws the simulation speed and the cell under the mouse in the bottom left corner
+    pub fn draw<const W: usize, const H: usize>(&self, canvas: &mut Canvas<Window>, font: &Font, game: &Game<W, H>, area_height: u32) {
+        let line = font.line_height(OVERLAY_SCALE) as i32;
+        let mut y = area_height as i32 - OVERLAY_MARGIN - line * 2;
+
+        let speed = if self.frozen { "Frozen".to_string() } else { format!("Speed 1/{}", SLOWDOWNS[self.slowdown]) };
+        font.draw(canvas, &format!("{}  Tick {}", speed, game.ticks()), (OVERLAY_MARGIN, y), OVERLAY_SCALE, OVERLAY_COLOUR);
+        y += line;
+
+        if let Some(cell) = self.mouse.and_then(|mouse| game.cell_at(mouse)) {
+            let description = format!("{}, {}: {:?}", cell.0, cell.1, game.cell(cell));
+            font.draw(canvas, &description, (OVERLAY_MARGIN, y), OVERLAY_SCALE, OVERLAY_COLOUR);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use sdl2::render::Canvas;
+use sdl2::video::{FullscreenType, Window};
+
+/// Scales everything drawn to the canvas so that `design_size` always fits in the window
+pub struct Display {
+    design_size: (u32, u32),
+    fullscreen: bool,
+    integer_scale: bool,
+    scale: f32,
+    logical_size: (u32, u32),
+}
+
+impl Display {
+    pub fn new(design_size: (u32, u32)) -> Self {
+        Self {
+            design_size,
+            fullscreen: false,
+            integer_scale: true,
+            scale: 1.0,
+            logical_size: design_size
+        }
+    }
+
+    pub fn logical_size(&self) -> (u32, u32) {
+        self.logical_size
+    }
+
+    pub fn toggle_fullscreen(&mut self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        self.fullscreen = !self.fullscreen;
+        let fullscreen_type = if self.fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+        canvas.window_mut().set_fullscreen(fullscreen_type)
+    }
+
+    pub fn toggle_integer_scale(&mut self, canvas: &mut Canvas<Window>) -> Result<(u32, u32), String> {
+        self.integer_scale = !self.integer_scale;
+        self.update(canvas)
+    }
+
+    /// Recomputes the scale from the current window size and returns the new logical size.
+    /// Integer scaling keeps pixels crisp but falls back to fractional if the window is smaller than the design size
+    pub fn update(&mut self, canvas: &mut Canvas<Window>) -> Result<(u32, u32), String> {
+        let output_size = canvas.output_size()?;
+        let scale = (output_size.0 as f32 / self.design_size.0 as f32).min(output_size.1 as f32 / self.design_size.1 as f32);
+
+        self.scale = if self.integer_scale && scale >= 1.0 { scale.floor() } else { scale };
+        self.logical_size = ((output_size.0 as f32 / self.scale) as u32, (output_size.1 as f32 / self.scale) as u32);
+
+        canvas.set_scale(self.scale, self.scale)?;
+        Ok(self.logical_size)
+    }
+
+    /// Converts a position in window pixels, such as the mouse position, to logical coordinates
+    pub fn to_logical(&self, position: (i32, i32)) -> (i32, i32) {
+        ((position.0 as f32 / self.scale) as i32, (position.1 as f32 / self.scale) as i32)
+    }
+}
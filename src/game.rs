@@ -1,15 +1,33 @@
-mod tetromino;
-
-use std::collections::{HashMap, HashSet, VecDeque};
-use rand::distributions::{Distribution, Standard};
+pub mod tetromino;
+pub mod level;
+pub mod render;
+pub mod palette;
+mod effects;
+pub mod assist;
+pub mod clear;
+pub mod mode;
+pub mod random;
+pub mod save;
+pub mod snapshot;
+pub mod puzzle;
+pub mod sandbox;
+
+use std::time::Duration;
 use rand::Rng;
-use rand::rngs::ThreadRng;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::surface::Surface;
+use sdl2::render::{Canvas, Texture};
 use sdl2::video::Window;
-use crate::game::tetromino::Tetromino;
+use crate::game::assist::{Assist, RegionPreview};
+use crate::game::effects::Effects;
+use crate::game::level::Gravity;
+use crate::game::palette::{MAX_COLOURS, Palette};
+use crate::game::render::RenderBackend;
+use crate::game::clear::{ClearDetector, ClearRule};
+use crate::game::mode::{EndCondition, GameMode, TopOut, ticks_to_duration};
+use crate::game::puzzle::{Goal, Puzzle, PuzzleState};
+use crate::game::random::GameRng;
+use crate::game::tetromino::{Tetromino, TetrominoShape};
 
 pub struct Toggle {
     toggle: bool
@@ -26,38 +44,17 @@ impl Toggle {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub enum ColourCode {
-    Red,
-    Green,
-    Blue,
-    Violet,
-}
+/// Index of a colour in the game's palette
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ColourCode(pub u8);
 
 impl ColourCode {
-    pub fn to_rgb(&self) -> Color {
-        match self {
-            ColourCode::Blue => Color::BLUE,
-            ColourCode::Red => Color::RED,
-            ColourCode::Green => Color::GREEN,
-            ColourCode::Violet => Color::RGB(195, 0, 255),
-        }
-    }
-}
-
-impl Distribution<ColourCode> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> ColourCode {
-        match rng.gen_range(0..4) {
-            0 => ColourCode::Red,
-            1 => ColourCode::Green,
-            2 => ColourCode::Blue,
-            3 => ColourCode::Violet,
-            _ => panic!()
-        }
+    pub fn index(&self) -> usize {
+        self.0 as usize
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ColourType {
     Empty,
     Colour(ColourCode),
@@ -66,23 +63,30 @@ pub enum ColourType {
 }
 
 impl ColourType {
-    pub fn to_rgb(&self, background: Color) -> Color {
+    pub fn to_rgb(&self, palette: &Palette, background: Color) -> Color {
         match self {
             ColourType::Empty => background,
             ColourType::Colour(code) | ColourType::NoPhysicsColour(code) => {
-                code.to_rgb()
+                palette.colour(code.index())
             }
             ColourType::Deleting(code, time_left) => {
                 if (time_left / 10) % 2 == 1 {
                     background
                 }
                 else {
-                    code.to_rgb()
+                    palette.colour(code.index())
                 }
             }
         }
     }
 
+    pub fn code(&self) -> Option<ColourCode> {
+        match self {
+            ColourType::Empty => None,
+            ColourType::Colour(code) | ColourType::NoPhysicsColour(code) | ColourType::Deleting(code, _) => Some(*code)
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         match self {
             ColourType::Empty => true,
@@ -117,6 +121,39 @@ impl ColourType {
 }
 
 
+/// Things that happened during an update, drained by the caller with `Game::take_events`
+#[derive(Copy, Clone, Debug)]
+pub enum GameEvent {
+    Move,
+    Rotate,
+    Lock,
+    /// A region started flashing, removing `pixels` pixels
+    Clear { pixels: usize },
+    /// A clear followed another without a piece locking in between
+    Combo(u32),
+    GameOver,
+    /// The stack reached the top in a mode without game over, and its top was cleared
+    Overflow,
+    /// The goal of the puzzle being played was met, or a timed mode finished
+    Won,
+}
+
+/// Largest change in brightness from a pixel's shade
+const GRAIN_VARIATION: f32 = 0.15;
+
+fn shade(colour: Color, shade: i8) -> Color {
+    let factor = 1.0 + GRAIN_VARIATION * shade as f32 / i8::MAX as f32;
+    Color::RGB(
+        (colour.r as f32 * factor).min(255.0) as u8,
+        (colour.g as f32 * factor).min(255.0) as u8,
+        (colour.b as f32 * factor).min(255.0) as u8
+    )
+}
+
+pub const MIN_COLOURS: usize = 2;
+/// Rows of squares cleared from the top of the stack when it overflows
+const OVERFLOW_SQUARES: u32 = 6;
+pub const DEFAULT_COLOURS: usize = 4;
 
 pub struct Game<const W: usize, const H: usize> {
     pixel_size: u32,
@@ -126,9 +163,40 @@ pub struct Game<const W: usize, const H: usize> {
     square_width: u32,
     square_height: u32,
     background: Color,
+    mode: GameMode,
+    puzzle: Option<PuzzleState>,
+    seed: u64,
+    rng: GameRng,
+    palette: Palette,
+    colour_count: usize,
     board: [[ColourType; H]; W],
+    /// Brightness variation of each pixel, moved along with it so sand looks granular
+    shades: [[i8; H]; W],
     tetromino: Option<Tetromino>,
-    time_since_last: u64
+    time_since_last: u64,
+    level: u32,
+    clears: u32,
+    pixels_cleared: u64,
+    score: u64,
+    pieces_placed: u32,
+    chain: u32,
+    /// Game updates run since the start, the in-game timer
+    ticks: u64,
+    stack_height: usize,
+    /// Whether the last physics update left every pixel where it was
+    settled: bool,
+    /// Set when the stack tops out or the game finishes, after which no more pieces spawn
+    over: bool,
+    events: Vec<GameEvent>,
+    effects: Effects,
+    assist: Assist,
+    clear_detector: ClearDetector,
+    clear_rule: ClearRule,
+    /// Rules games of each mode start with, for modes that don't use the default
+    clear_rules: Vec<(GameMode, ClearRule)>,
+    render_backend: RenderBackend,
+    texture: Option<Texture>,
+    frame: Vec<u8>
 }
 
 impl<const W: usize, const H: usize> Game<W, H> {
@@ -142,6 +210,10 @@ impl<const W: usize, const H: usize> Game<W, H> {
             panic!("Square pixel width must fit into width and height");
         }
 
+        if pixel_size == 0 {
+            panic!("Pixel size must be at least 1");
+        }
+
         Self {
             pixel_size,
             screen_size,
@@ -150,157 +222,322 @@ impl<const W: usize, const H: usize> Game<W, H> {
             square_width: W as u32 / square_pixel_width,
             square_height: H as u32 / square_pixel_width,
             background,
+            mode: GameMode::Endless,
+            puzzle: None,
+            seed: 0,
+            rng: GameRng::new(0),
+            palette: Palette::built_in("classic").unwrap(),
+            colour_count: DEFAULT_COLOURS,
             board: [[ColourType::Empty; H]; W],
+            shades: [[0; H]; W],
             tetromino: None,
-            time_since_last: 0
+            time_since_last: 0,
+            level: 0,
+            clears: 0,
+            pixels_cleared: 0,
+            score: 0,
+            pieces_placed: 0,
+            chain: 0,
+            ticks: 0,
+            stack_height: 0,
+            settled: false,
+            over: false,
+            events: Vec::new(),
+            effects: Effects::new(),
+            assist: Assist::new(),
+            clear_detector: ClearDetector::new(),
+            clear_rule: ClearRule::default(),
+            clear_rules: Vec::new(),
+            render_backend: RenderBackend::Texture,
+            texture: None,
+            frame: Vec::new()
         }
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) {
-        if self.pixel_size != 0 {
-            let mut pixel_data = Vec::with_capacity((W * self.pixel_size as usize) * (H * self.pixel_size as usize) * 3);
+    pub fn level(&self) -> u32 {
+        self.level
+    }
 
+    pub fn clears(&self) -> u32 {
+        self.clears
+    }
 
-            for y in (0..H).rev() {
-                for _ in 0..self.pixel_size {
-                    for x in 0..W {
-                        let colour = self.board[x][y].to_rgb(self.background).rgb();
-                        for _ in 0..self.pixel_size {
-                            pixel_data.push(colour.0);
-                            pixel_data.push(colour.1);
-                            pixel_data.push(colour.2);
-                        }
-                    }
-                }
-            }
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn pieces_placed(&self) -> u32 {
+        self.pieces_placed
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
 
-            let surface = Surface::from_data(&mut pixel_data,
-                                             W as u32 * self.pixel_size,
-                                             H as u32 * self.pixel_size,
-                                             (3 * W) as u32 * self.pixel_size,
-                                             sdl2::pixels::PixelFormatEnum::RGB24
-            ).unwrap();
+    /// Time played, counted in game updates so it doesn't drift with the frame rate or stop during hitches
+    pub fn elapsed(&self) -> Duration {
+        ticks_to_duration(self.ticks)
+    }
 
-            canvas.copy(&surface.as_texture(&canvas.texture_creator()).unwrap(), None, Rect::new(self.screen_position.0, self.screen_position.1, W as u32 * self.pixel_size, H as u32 * self.pixel_size)).unwrap();
+    /// Time until a timed mode ends
+    pub fn time_left(&self) -> Option<Duration> {
+        match self.mode.end_condition() {
+            EndCondition::Ticks(ticks) => Some(ticks_to_duration(ticks.saturating_sub(self.ticks))),
+            _ => None
         }
-        else {
-            for x in 0..W {
-                for y in 0..H {
-                    let colour = self.board[x][y].to_rgb(self.background);
-                    canvas.set_draw_color(colour);
+    }
 
-                    if self.pixel_size == 1 {
-                        canvas.draw_point((self.screen_position.0 + x as i32, self.screen_position.1 + (H - y - 1) as i32)).unwrap();
-                    }
-                    else {
-                        canvas.fill_rect(
-                            Rect::new(
-                                self.screen_position.0 + (x as u32 * self.pixel_size) as i32, self.screen_position.1 + ((H - y - 1) as u32 * self.pixel_size) as i32,
-                                self.pixel_size, self.pixel_size
-                            )
-                        ).unwrap();
-                    }
-                }
-            }
+    /// Height of the tallest sand pixel, settled or still falling, as a fraction of the height that ends the game.
+    /// The falling piece isn't counted
+    pub fn danger(&self) -> f32 {
+        self.stack_height as f32 / self.game_over_line() as f32
+    }
+
+    fn game_over_line(&self) -> usize {
+        (19 * self.square_pixel_width) as usize
+    }
+
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn gravity(&self) -> Gravity {
+        Gravity::for_level(self.mode.gravity_level(self.level), self.square_pixel_width)
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Seed of the generator that chose this game's pieces and colours
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts a new game of `mode` with pieces chosen from `seed` and the clear rule set for the mode, see `reset`
+    pub fn start(&mut self, mode: GameMode, seed: u64, frame_count: u64) {
+        self.mode = mode;
+        self.seed = seed;
+        self.puzzle = None;
+        self.clear_rule = self.clear_rules.iter().find(|(m, _)| *m == mode).map_or(ClearRule::default(), |(_, rule)| *rule);
+        self.reset(frame_count);
+    }
+
+    /// Starts `puzzle` from its starting board
+    pub fn start_puzzle(&mut self, puzzle: Puzzle, frame_count: u64) -> Result<(), String> {
+        let board = match &puzzle.board {
+            Some(path) => Some(snapshot::import_board::<W, H>(path, &puzzle::board_palette(), MAX_COLOURS)?),
+            None => None
+        };
+
+        self.start(GameMode::Puzzle, 0, frame_count);
+        if let Some(board) = board {
+            self.board = *board;
+            self.clear_detector.mark_all();
+            self.frame.clear();
         }
+        self.puzzle = Some(PuzzleState { puzzle, next_piece: 0 });
+        Ok(())
+    }
 
-        canvas.set_draw_color(Color::WHITE);
+    pub fn puzzle(&self) -> Option<&Puzzle> {
+        self.puzzle.as_ref().map(|state| &state.puzzle)
+    }
 
-        for y in -1..(H as i32) {
-            for x in [-1, W as i32] {
-                if self.pixel_size == 1 {
-                    canvas.draw_point((self.screen_position.0 + x, self.screen_position.1 + (H as i32 - y - 1))).unwrap();
-                }
-                else {
-                    canvas.fill_rect(
-                        Rect::new(
-                            self.screen_position.0 + (x * self.pixel_size as i32), self.screen_position.1 + ((H as i32 - y - 1) * self.pixel_size as i32),
-                            self.pixel_size, self.pixel_size
-                        )
-                    ).unwrap();
+    /// Pieces still to come in the puzzle being played
+    pub fn pieces_left(&self) -> Option<usize> {
+        self.puzzle.as_ref().map(|state| state.puzzle.pieces.len() - state.next_piece)
+    }
+
+    /// The next piece to drop, from the puzzle's sequence if there is one. None once a puzzle runs out
+    fn next_piece(&mut self) -> Option<(TetrominoShape, ColourCode)> {
+        match &mut self.puzzle {
+            Some(state) => {
+                let piece = state.puzzle.pieces.get(state.next_piece).copied();
+                if piece.is_some() {
+                    state.next_piece += 1;
                 }
+                piece
             }
+            None => Some((self.rng.gen(), ColourCode(self.rng.gen_range(0..self.colour_count) as u8)))
         }
+    }
 
-        for x in 0..(W as i32) {
-            let y = -1;
-            if self.pixel_size == 1 {
-                canvas.draw_point((self.screen_position.0 + x, self.screen_position.1 + (H as i32 - y - 1))).unwrap();
-            }
-            else {
-                canvas.fill_rect(
-                    Rect::new(
-                        self.screen_position.0 + (x * self.pixel_size as i32), self.screen_position.1 + ((H as i32 - y - 1) * self.pixel_size as i32),
-                        self.pixel_size, self.pixel_size
-                    )
-                ).unwrap();
-            }
+    /// Finishes the game once the mode's end condition is met
+    fn check_end_condition(&mut self) {
+        let finished = match self.mode.end_condition() {
+            EndCondition::None => false,
+            EndCondition::Clears(clears) => self.clears >= clears,
+            EndCondition::Ticks(ticks) => self.ticks >= ticks,
+        };
+
+        if finished {
+            self.over = true;
+            self.events.push(GameEvent::Won);
         }
     }
 
-    fn ant(&mut self, cell: (usize, usize), target_colour: ColourCode, origins_visited: &mut HashSet<usize>) -> (bool, HashSet<(usize, usize)>) {
-        fn check_cell_colour(current: ColourType, target: ColourCode) -> bool {
-            match current {
-                ColourType::Colour(colour) => colour == target,
-                _ => false
-            }
+    /// Ends a puzzle once its goal is met, or once the pieces run out and the sand has settled without meeting it
+    fn check_puzzle(&mut self) {
+        let state = match &self.puzzle {
+            Some(state) => state,
+            None => return
+        };
+
+        let solved = match state.puzzle.goal {
+            Goal::ClearPixels(pixels) => self.pixels_cleared >= pixels,
+            Goal::ClearColour(code) => self.tetromino.is_none() && !self.board.iter().flatten().any(|cell| cell.code() == Some(code)),
+            Goal::SurvivePieces(pieces) => self.pieces_placed >= pieces,
+        };
+        let out_of_pieces = state.next_piece >= state.puzzle.pieces.len() && self.tetromino.is_none() && self.settled
+            && !self.board.iter().flatten().any(|cell| matches!(cell, ColourType::Deleting(_, _)));
+
+        if solved {
+            self.over = true;
+            self.events.push(GameEvent::Won);
         }
+        else if out_of_pieces {
+            self.over = true;
+            self.events.push(GameEvent::GameOver);
+        }
+    }
 
-        let mut open_set = VecDeque::new();
-        open_set.push_back(cell);
-        let mut history = HashSet::new();
-        history.insert(cell);
-        let mut found = false;
+    /// Empties the board and starts a new game with the same settings. The first piece spawns after the usual delay from `frame_count`
+    pub fn reset(&mut self, frame_count: u64) {
+        self.board = [[ColourType::Empty; H]; W];
+        self.shades = [[0; H]; W];
+        self.tetromino = None;
+        self.rng = GameRng::new(self.seed);
+        self.time_since_last = frame_count;
+        self.level = 0;
+        self.clears = 0;
+        self.pixels_cleared = 0;
+        self.score = 0;
+        self.pieces_placed = 0;
+        self.chain = 0;
+        self.ticks = 0;
+        self.stack_height = 0;
+        self.settled = false;
+        self.over = false;
+        self.events.clear();
+        let screen_shake = self.effects.screen_shake;
+        self.effects = Effects::new();
+        self.effects.screen_shake = screen_shake;
+        self.clear_detector.mark_all();
+        self.frame.clear();
+    }
 
-        loop {
-            let cell = match open_set.pop_front() {
-                Some(c) => c,
-                None => break
-            };
+    /// Resizes the screen the board is drawn on, keeping the board centred
+    pub fn set_layout(&mut self, screen_size: (u32, u32)) {
+        self.screen_size = screen_size;
+        self.screen_position = (
+            (screen_size.0 as i32 - (W as u32 * self.pixel_size) as i32) / 2,
+            (screen_size.1 as i32 - (H as u32 * self.pixel_size) as i32) / 2
+        );
+    }
 
-            if cell.0 < W - 1 {
-                let cell = (cell.0 + 1, cell.1);
+    /// Area of the screen covered by the board, excluding the border
+    pub fn board_rect(&self) -> Rect {
+        Rect::new(self.screen_position.0, self.screen_position.1, W as u32 * self.pixel_size, H as u32 * self.pixel_size)
+    }
 
-                if check_cell_colour(self.board[cell.0][cell.1], target_colour) && !history.contains(&cell) {
-                    history.insert(cell);
-                    open_set.push_back(cell);
-                    if cell.0 == W - 1 { found = true; }
-                    else if cell.0 == 0 { origins_visited.insert(cell.1); }
-                }
-            }
-            if cell.0 > 0 {
-                let cell = (cell.0 - 1, cell.1);
-
-                if check_cell_colour(self.board[cell.0][cell.1], target_colour) && !history.contains(&cell) {
-                    history.insert(cell);
-                    open_set.push_back(cell);
-                    if cell.0 == W - 1 { found = true; }
-                    else if cell.0 == 0 { origins_visited.insert(cell.1); }
-                }
-            }
-            if cell.1 < H - 1 {
-                let cell = (cell.0, cell.1 + 1);
-
-                if check_cell_colour(self.board[cell.0][cell.1], target_colour) && !history.contains(&cell) {
-                    history.insert(cell);
-                    open_set.push_back(cell);
-                    if cell.0 == W - 1 { found = true; }
-                    else if cell.0 == 0 { origins_visited.insert(cell.1); }
-                }
-            }
-            if cell.1 > 0 {
-                let cell = (cell.0, cell.1 - 1);
-
-                if check_cell_colour(self.board[cell.0][cell.1], target_colour) && !history.contains(&cell) {
-                    history.insert(cell);
-                    open_set.push_back(cell);
-                    if cell.0 == W - 1 { found = true; }
-                    else if cell.0 == 0 { origins_visited.insert(cell.1); }
-                }
+    /// Where the board is drawn this frame, which is `board_rect` moved by any screen shake.
+    /// Only used for drawing, so the mouse still maps to the cells that are really there
+    fn draw_rect(&self) -> Rect {
+        let shake = self.effects.shake_offset();
+        let mut rect = self.board_rect();
+        rect.offset(shake.0, shake.1);
+        rect
+    }
+
+    pub fn assist_enabled(&self) -> bool {
+        self.assist.enabled
+    }
+
+    /// Highlights the largest region of each colour every frame to help learn the clear rule
+    pub fn set_assist(&mut self, enabled: bool) {
+        self.assist.enabled = enabled;
+        self.clear_detector.set_track_regions(enabled);
+        if enabled {
+            self.clear_detector.mark_all();
+        }
+    }
+
+    /// Sets what counts as a clear in games of `mode`, from the next game started
+    pub fn set_clear_rule(&mut self, mode: GameMode, rule: ClearRule) {
+        self.clear_rules.retain(|(m, _)| *m != mode);
+        self.clear_rules.push((mode, rule));
+    }
+
+    pub fn assist_previews(&self) -> &[RegionPreview] {
+        self.assist.previews()
+    }
+
+    pub fn screen_shake(&self) -> bool {
+        self.effects.screen_shake
+    }
+
+    pub fn set_screen_shake(&mut self, screen_shake: bool) {
+        self.effects.screen_shake = screen_shake;
+    }
+
+    /// Advances clear animations and particles. These are cosmetic and don't affect the simulation
+    pub fn update_effects(&mut self) {
+        self.effects.update();
+    }
+
+    pub fn render_backend(&self) -> RenderBackend {
+        self.render_backend
+    }
+
+    pub fn set_render_backend(&mut self, render_backend: RenderBackend) {
+        self.render_backend = render_backend;
+        // Other backends don't keep the texture's copy of the frame up to date
+        self.frame.clear();
+    }
+
+    pub fn colour_count(&self) -> usize {
+        self.colour_count
+    }
+
+    /// Sets how many colours pieces are spawned in. Fewer colours make clears easier but score less
+    pub fn set_colour_count(&mut self, colour_count: usize) {
+        if !(MIN_COLOURS..=MAX_COLOURS).contains(&colour_count) {
+            panic!("Colour count must be between {} and {}", MIN_COLOURS, MAX_COLOURS);
+        }
+        self.colour_count = colour_count;
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    fn pixel_colour(&self, x: usize, y: usize) -> Color {
+        let cell = self.board[x][y];
+        let colour = cell.to_rgb(&self.palette, self.background);
+        match cell.code() {
+            Some(code) if colour != self.background => {
+                let colour = shade(colour, self.shades[x][y]);
+                self.palette.apply_pattern(colour, code.index(), x, y)
             }
+            _ => colour
         }
+    }
 
-        (found, history)
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>) {
+        match self.render_backend {
+            RenderBackend::Texture => self.draw_texture(canvas),
+            RenderBackend::Rects => self.draw_rects(canvas),
+            RenderBackend::Software => self.draw_software(canvas),
+        }
+
+        self.draw_border(canvas);
+        self.assist.draw(canvas, self.draw_rect(), self.pixel_size, (W, H));
+        self.effects.draw(canvas, self.draw_rect(), self.pixel_size, H);
     }
 
     // TODO: Collect all movements into one method
@@ -330,8 +567,10 @@ impl<const W: usize, const H: usize> Game<W, H> {
                 for pos in tetromino.get_all_pixels(self.square_pixel_width) {
                     let pos = (pos.0 as usize, pos.1 as usize);
                     self.board[pos.0 - moved][pos.1] = self.board[pos.0][pos.1];
+                    self.shades[pos.0 - moved][pos.1] = self.shades[pos.0][pos.1];
                     self.board[pos.0][pos.1] = ColourType::Empty;
                 }
+                self.events.push(GameEvent::Move);
             }
 
             tetromino.position = (tetromino.position.0 - moved as i64, tetromino.position.1);
@@ -366,6 +605,9 @@ impl<const W: usize, const H: usize> Game<W, H> {
                 let start_pos = (start_pos.0 as usize, start_pos.1 as usize);
 
                 let colour = self.board[start_pos.0][start_pos.1];
+                let shades: Vec<i8> = tetromino.get_all_pixels(self.square_pixel_width)
+                    .map(|pos| self.shades[pos.0 as usize][pos.1 as usize])
+                    .collect();
                 self.board[start_pos.0][start_pos.1] = ColourType::Empty;
 
                 for pos in iter {
@@ -373,10 +615,12 @@ impl<const W: usize, const H: usize> Game<W, H> {
                     self.board[pos.0][pos.1] = ColourType::Empty;
                 }
 
-                for pos in tetromino.get_all_pixels(self.square_pixel_width) {
+                for (pos, shade) in tetromino.get_all_pixels(self.square_pixel_width).zip(shades) {
                     let pos = (pos.0 as usize, pos.1 as usize);
                     self.board[pos.0 + moved][pos.1] = colour;
+                    self.shades[pos.0 + moved][pos.1] = shade;
                 }
+                self.events.push(GameEvent::Move);
             }
 
             tetromino.position = (tetromino.position.0 + moved as i64, tetromino.position.1);
@@ -422,7 +666,9 @@ impl<const W: usize, const H: usize> Game<W, H> {
                 }
                 else {
                     self.board[pos.0][pos.1 - moved] = self.board[pos.0][pos.1].with_physics();
+                    self.clear_detector.mark((pos.0, pos.1 - moved));
                 }
+                self.shades[pos.0][pos.1 - moved] = self.shades[pos.0][pos.1];
                 self.board[pos.0][pos.1] = ColourType::Empty;
             }
         }
@@ -430,11 +676,15 @@ impl<const W: usize, const H: usize> Game<W, H> {
             for pos in tetromino.get_all_pixels(self.square_pixel_width) {
                 let pos = (pos.0 as usize, pos.1 as usize);
                 self.board[pos.0][pos.1 - moved] = self.board[pos.0][pos.1].with_physics();
+                self.clear_detector.mark((pos.0, pos.1 - moved));
             }
         }
 
         if collided {
             self.tetromino = None;
+            self.pieces_placed += 1;
+            self.chain = 0;
+            self.events.push(GameEvent::Lock);
         }
         else {
             tetromino.position = (tetromino.position.0, tetromino.position.1 - moved as i64);
@@ -477,6 +727,9 @@ impl<const W: usize, const H: usize> Game<W, H> {
         let start_pos = (start_pos.0 as usize, start_pos.1 as usize);
 
         let colour = self.board[start_pos.0][start_pos.1];
+        let shades: Vec<i8> = tetromino.get_all_pixels(self.square_pixel_width)
+            .map(|pos| self.shades[pos.0 as usize][pos.1 as usize])
+            .collect();
         self.board[start_pos.0][start_pos.1] = ColourType::Empty;
 
         for pos in iter {
@@ -486,59 +739,92 @@ impl<const W: usize, const H: usize> Game<W, H> {
 
         tetromino.rotation = modified_rotation;
 
-        for pos in tetromino.get_all_pixels(self.square_pixel_width) {
+        for (pos, shade) in tetromino.get_all_pixels(self.square_pixel_width).zip(shades) {
             let pos = (pos.0 as usize, pos.1 as usize);
             self.board[pos.0][pos.1] = colour;
+            self.shades[pos.0][pos.1] = shade;
         }
+        self.events.push(GameEvent::Rotate);
     }
 
-    pub fn game_update(&mut self, rng: &mut ThreadRng, frame_count: u64) {
-        if self.tetromino.is_none() && frame_count - self.time_since_last > 30 {
-            let tetromino = Tetromino::new(rng.gen(), ((self.square_pixel_width * ((self.square_width / 2) - 2)) as i64, self.square_pixel_width as i64 * 19), 0);
-            let colour = ColourType::NoPhysicsColour(rng.gen());
+    pub fn game_update(&mut self, frame_count: u64) {
+        if self.over { return; }
+
+        self.ticks += 1;
+        let gravity = self.gravity();
+
+        let spawn_due = self.mode.spawns_pieces() && self.tetromino.is_none() && frame_count - self.time_since_last > level::spawn_delay(self.level);
+        let piece = if spawn_due { self.next_piece() } else { None };
+        if let Some((shape, colour_code)) = piece {
+            let tetromino = Tetromino::new(shape, ((self.square_pixel_width * ((self.square_width / 2) - 2)) as i64, self.square_pixel_width as i64 * 19), 0);
+            let colour = ColourType::NoPhysicsColour(colour_code);
             for pos in tetromino.get_all_pixels(self.square_pixel_width) {
                 let pos = (pos.0 as usize, pos.1 as usize);
                 self.board[pos.0][pos.1] = colour;
+                self.shades[pos.0][pos.1] = self.rng.gen();
             }
             self.tetromino = Some(tetromino);
             self.time_since_last = frame_count
         }
         else if self.tetromino.is_some() {
-            if (frame_count - self.time_since_last) % 30 == 29 {
-                self.move_down();
+            if (frame_count - self.time_since_last) % gravity.frames_per_drop == gravity.frames_per_drop - 1 {
+                self.move_down_amount(gravity.drop_amount);
                 if self.tetromino.is_none() {
                     self.time_since_last = frame_count;
                 }
             }
         }
 
-        let mut origins_visited: HashSet<usize> = HashSet::new();
-        for y in 0..H {
-            let colour_code = match self.board[0][y] {
-                ColourType::Colour(code) => code,
-                _ => continue,
-            };
-            if origins_visited.contains(&y) { continue; }
-
-            let (found, visited) = self.ant((0, y), colour_code, &mut origins_visited);
-
-            if found {
-                for pos in visited {
-                    self.board[pos.0][pos.1].set_deleting(40);
-                }
+        for (colour_code, region) in self.clear_detector.find_clears(&self.board, &self.clear_rule) {
+            self.clears += 1;
+            self.pixels_cleared += region.len() as u64;
+            self.score += region.len() as u64 * (self.level as u64 + 1) * self.colour_count as u64 / DEFAULT_COLOURS as u64;
+            self.chain += 1;
+            self.events.push(GameEvent::Clear { pixels: region.len() });
+            if self.chain > 1 {
+                self.events.push(GameEvent::Combo(self.chain));
             }
+            for pos in &region {
+                self.board[pos.0][pos.1].set_deleting(40);
+            }
+            self.effects.start_clear(region, self.palette.colour(colour_code.index()), W);
         }
+
+        self.level = level::level_for(self.clears, self.pixels_cleared, (W * self.square_pixel_width as usize) as u64);
+        self.assist.update(&self.board, &mut self.clear_detector, &self.clear_rule);
+        self.check_puzzle();
+        self.check_end_condition();
     }
 
     fn move_pixel(&mut self, from: (usize, usize), to: (usize, usize)) {
         self.board[to.0][to.1] = self.board[from.0][from.1];
+        self.shades[to.0][to.1] = self.shades[from.0][from.1];
+        self.clear_detector.mark(to);
         self.board[from.0][from.1] = ColourType::Empty;
+        self.settled = false;
+    }
+
+    /// Clears the top of the stack instead of ending the game, for modes that don't top out
+    fn overflow(&mut self) {
+        let line = self.game_over_line() - (OVERFLOW_SQUARES * self.square_pixel_width) as usize;
+        for y in line..H {
+            for x in 0..W {
+                if self.board[x][y].is_physics() {
+                    // Higher pixels go first so the top melts away downwards
+                    self.board[x][y].set_deleting((H - y) as u32 / 10);
+                }
+            }
+        }
+        self.events.push(GameEvent::Overflow);
+        self.stack_height = line;
     }
 
     pub fn physics_update(&mut self) {
         let mut toggle = Toggle::new();
+        self.settled = true;
 
         let mut game_over = false;
+        let mut stack_height = 0;
 
         for y in 0..H {
             for x in 0..W {
@@ -552,7 +838,9 @@ impl<const W: usize, const H: usize> Game<W, H> {
                         }
                     },
                     ColourType::Colour(_) => {
-                        if y >= (19 * self.square_pixel_width) as usize {
+                        stack_height = y + 1;
+                        // A finished game can't also top out, which would replace its results
+                        if y >= self.game_over_line() && !self.over {
                             game_over = true;
                         }
                     }
@@ -560,6 +848,17 @@ impl<const W: usize, const H: usize> Game<W, H> {
                 }
             }
 
+            if game_over {
+                match self.mode.top_out() {
+                    TopOut::GameOver => {}
+                    TopOut::Overflow => {
+                        self.overflow();
+                        return;
+                    }
+                    TopOut::Ignore => game_over = false
+                }
+            }
+
             if game_over {
                 for y in 0..H {
                     for x in 0..W {
@@ -576,6 +875,9 @@ impl<const W: usize, const H: usize> Game<W, H> {
                         }
                     }
                 }
+                self.events.push(GameEvent::GameOver);
+                self.stack_height = 0;
+                self.over = true;
                 return;
             }
 
@@ -644,5 +946,7 @@ impl<const W: usize, const H: usize> Game<W, H> {
                 }
             }
         }
+
+        self.stack_height = stack_height;
     }
 }
\ No newline at end of file
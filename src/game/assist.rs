@@ -0,0 +1,107 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use crate::game::{ColourCode, ColourType};
+use crate::game::clear::{ClearCondition, ClearDetector, ClearRule, neighbour_offsets};
+
+const MIN_OUTLINE_ALPHA: f32 = 80.0;
+
+/// The largest settled region of one colour
+pub struct RegionPreview {
+    pub code: ColourCode,
+    pub size: usize,
+    /// How close the region is to clearing under the clear rule, 1.0 when it would clear
+    pub progress: f32,
+    cells: Vec<(usize, usize)>,
+    outline: Vec<(usize, usize)>,
+}
+
+/// Optional help for new players showing the largest region of each colour and how close it is to being cleared
+pub struct Assist {
+    pub enabled: bool,
+    previews: Vec<RegionPreview>,
+}
+
+impl Assist {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            previews: Vec::new()
+        }
+    }
+
+    pub fn previews(&self) -> &[RegionPreview] {
+        &self.previews
+    }
+
+    /// Keeps the largest region of each colour out of those `clear_detector` found since the last update, which
+    /// needs to be tracking regions. Only regions with a changed pixel are searched, so when a kept region loses a
+    /// pixel the whole board is searched again on the next tick
+    pub fn update<const W: usize, const H: usize>(&mut self, board: &[[ColourType; H]; W], clear_detector: &mut ClearDetector, rule: &ClearRule) {
+        if !self.enabled {
+            self.previews.clear();
+            return;
+        }
+
+        if clear_detector.scanned_all() {
+            self.previews.clear();
+        }
+        for (code, cells) in clear_detector.take_regions() {
+            let best = self.previews.iter().position(|p| p.code == code);
+            if best.is_some_and(|best| self.previews[best].size > cells.len()) { continue; }
+
+            let preview = preview(board, code, cells, rule);
+            match best {
+                Some(best) => self.previews[best] = preview,
+                None => self.previews.push(preview)
+            }
+        }
+        self.previews.sort_by_key(|p| p.code.index());
+
+        let stale = self.previews.iter()
+            .any(|p| p.cells.iter().any(|&(x, y)| board[x][y] != ColourType::Colour(p.code)));
+        if stale {
+            clear_detector.mark_all();
+        }
+    }
+
+    /// Outlines each region, more strongly the closer it is to clearing
+    pub fn draw(&self, canvas: &mut Canvas<Window>, board: Rect, pixel_size: u32, board_size: (usize, usize)) {
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        for preview in &self.previews {
+            let rects: Vec<Rect> = preview.outline.iter().map(|&(x, y)| Rect::new(
+                board.left() + (x as u32 * pixel_size) as i32, board.top() + ((board_size.1 - y - 1) as u32 * pixel_size) as i32,
+                pixel_size, pixel_size
+            )).collect();
+            if rects.is_empty() { continue; }
+
+            let alpha = MIN_OUTLINE_ALPHA + (255.0 - MIN_OUTLINE_ALPHA) * preview.progress;
+            canvas.set_draw_color(Color::RGBA(255, 255, 255, alpha as u8));
+            canvas.fill_rects(&rects).unwrap();
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+fn preview<const W: usize, const H: usize>(board: &[[ColourType; H]; W], code: ColourCode, cells: Vec<(usize, usize)>, rule: &ClearRule) -> RegionPreview {
+    let size = cells.len();
+    let progress = match rule.condition {
+        ClearCondition::WallToWall | ClearCondition::FullRows => {
+            let left = cells.iter().map(|cell| cell.0).min().unwrap_or(0);
+            let right = cells.iter().map(|cell| cell.0).max().unwrap_or(0);
+            (right - left + 1) as f32 / W as f32
+        }
+        ClearCondition::MinimumSize(minimum) => (size as f32 / minimum.max(1) as f32).min(1.0)
+    };
+
+    // Cells next to anything but the region's own colour
+    let outline = cells.iter().copied().filter(|cell| neighbour_offsets(rule.connectivity).iter().any(|offset| {
+        let neighbour = ((cell.0 as isize + offset.0) as usize, (cell.1 as isize + offset.1) as usize);
+        neighbour.0 < W && neighbour.1 < H && board[neighbour.0][neighbour.1] != ColourType::Colour(code)
+    })).collect();
+
+    RegionPreview { code, size, progress, cells, outline }
+}
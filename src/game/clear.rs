@@ -0,0 +1,400 @@
+use crate::game::{ColourCode, ColourType};
+
+const DEFAULT_MINIMUM_SIZE: usize = 8_000;
+
+/// Pixels to clear and the colour to show them clearing in
+pub type Clear = (ColourCode, Vec<(usize, usize)>);
+/// A same coloured region of pixels with physics that didn't clear
+pub type Region = (ColourCode, Vec<(usize, usize)>);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ClearCondition {
+    /// A same coloured region touching both walls, the original rule
+    WallToWall,
+    /// Any same coloured region of at least this many pixels
+    MinimumSize(usize),
+    /// Every pixel in a row filled with any colour, like classic Tetris
+    FullRows,
+}
+
+/// Which pixels count as touching when growing a region
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Connectivity {
+    Four,
+    /// Diagonals count as touching too
+    Eight,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ClearRule {
+    pub condition: ClearCondition,
+    pub connectivity: Connectivity,
+}
+
+impl Default for ClearRule {
+    fn default() -> Self {
+        Self {
+            condition: ClearCondition::WallToWall,
+            connectivity: Connectivity::Four
+        }
+    }
+}
+
+impl ClearRule {
+    /// Parses `wall_to_wall`, `min_size`, `min_size:<pixels>` or `full_rows`, optionally followed by
+    /// `/4` or `/8` for the connectivity
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let (condition, connectivity) = match source.split_once('/') {
+            Some((condition, connectivity)) => (condition.trim(), Some(connectivity.trim())),
+            None => (source.trim(), None)
+        };
+
+        let condition = match condition.split_once(':') {
+            None if condition == "wall_to_wall" => ClearCondition::WallToWall,
+            None if condition == "full_rows" => ClearCondition::FullRows,
+            None if condition == "min_size" => ClearCondition::MinimumSize(DEFAULT_MINIMUM_SIZE),
+            Some(("min_size", size)) => ClearCondition::MinimumSize(
+                size.trim().parse().map_err(|_| format!("Invalid minimum size '{}'", size))?
+            ),
+            _ => return Err(format!("Unknown clear rule '{}'", condition))
+        };
+
+        let connectivity = match connectivity {
+            None | Some("4") => Connectivity::Four,
+            Some("8") => Connectivity::Eight,
+            Some(connectivity) => return Err(format!("Connectivity must be 4 or 8, found '{}'", connectivity))
+        };
+
+        Ok(Self { condition, connectivity })
+    }
+}
+
+/// Finds clears by searching only from pixels that changed since the last search.
+/// A clear can only form when one of its pixels changes, so settled regions are never searched twice
+pub struct ClearDetector {
+    /// Epoch each cell was last visited in, so the bitmap never needs clearing between searches
+    stamps: Vec<u32>,
+    /// Epoch each row was last checked in, for full row clears
+    row_stamps: Vec<u32>,
+    epoch: u32,
+    dirty: Vec<(usize, usize)>,
+    full_scan: bool,
+    scanned_all: bool,
+    open_set: Vec<(usize, usize)>,
+    region: Vec<(usize, usize)>,
+    /// Whether to keep the regions that didn't clear, for the assist. Regions are then grown under every condition
+    track_regions: bool,
+    regions: Vec<Region>,
+}
+
+impl ClearDetector {
+    pub fn new() -> Self {
+        Self {
+            stamps: Vec::new(),
+            row_stamps: Vec::new(),
+            epoch: 0,
+            dirty: Vec::new(),
+            full_scan: false,
+            scanned_all: false,
+            open_set: Vec::new(),
+            region: Vec::new(),
+            track_regions: false,
+            regions: Vec::new()
+        }
+    }
+
+    pub fn set_track_regions(&mut self, track_regions: bool) {
+        self.track_regions = track_regions;
+        self.regions.clear();
+    }
+
+    /// Regions searched since the last call that didn't clear. After a full scan these are all the regions on the
+    /// board, otherwise only those with a pixel that changed
+    pub fn take_regions(&mut self) -> Vec<Region> {
+        std::mem::take(&mut self.regions)
+    }
+
+    /// Whether the last search covered the whole board
+    pub fn scanned_all(&self) -> bool {
+        self.scanned_all
+    }
+
+    /// Records that a physics pixel arrived at `cell`
+    pub fn mark(&mut self, cell: (usize, usize)) {
+        self.dirty.push(cell);
+    }
+
+    /// Searches the whole board next time, for when the board or rule has been replaced wholesale
+    pub fn mark_all(&mut self) {
+        self.full_scan = true;
+        self.dirty.clear();
+    }
+
+    pub fn find_clears<const W: usize, const H: usize>(&mut self, board: &[[ColourType; H]; W], rule: &ClearRule) -> Vec<Clear> {
+        if self.stamps.len() != W * H {
+            self.stamps = vec![0; W * H];
+            self.row_stamps = vec![0; H];
+            self.epoch = 0;
+        }
+        self.epoch = self.epoch.wrapping_add(1);
+        if self.epoch == 0 {
+            self.stamps.fill(0);
+            self.row_stamps.fill(0);
+            self.epoch = 1;
+        }
+
+        let mut clears = Vec::new();
+
+        self.scanned_all = self.full_scan;
+        if self.full_scan {
+            self.full_scan = false;
+            for x in 0..W {
+                for y in 0..H {
+                    self.search_from(board, (x, y), rule, &mut clears);
+                }
+            }
+        }
+        else {
+            let dirty = std::mem::take(&mut self.dirty);
+            for cell in &dirty {
+                self.search_from(board, *cell, rule, &mut clears);
+            }
+            // Keep the allocation for next frame
+            self.dirty = dirty;
+            self.dirty.clear();
+        }
+
+        clears
+    }
+
+    fn search_from<const W: usize, const H: usize>(&mut self, board: &[[ColourType; H]; W], cell: (usize, usize), rule: &ClearRule, clears: &mut Vec<Clear>) {
+        let code = match board[cell.0][cell.1] {
+            ColourType::Colour(code) => code,
+            _ => return
+        };
+
+        if rule.condition == ClearCondition::FullRows {
+            if self.row_stamps[cell.1] != self.epoch {
+                self.row_stamps[cell.1] = self.epoch;
+
+                if (0..W).all(|x| board[x][cell.1].is_physics()) {
+                    clears.push((code, (0..W).map(|x| (x, cell.1)).collect()));
+                }
+            }
+            if !self.track_regions { return; }
+        }
+
+        if self.stamps[cell.0 * H + cell.1] == self.epoch { return; }
+
+        self.stamps[cell.0 * H + cell.1] = self.epoch;
+        self.open_set.push(cell);
+        self.region.clear();
+        let mut touches_left = false;
+        let mut touches_right = false;
+
+        while let Some(cell) = self.open_set.pop() {
+            self.region.push(cell);
+            if cell.0 == 0 { touches_left = true; }
+            if cell.0 == W - 1 { touches_right = true; }
+
+            for offset in neighbour_offsets(rule.connectivity) {
+                let neighbour = ((cell.0 as isize + offset.0) as usize, (cell.1 as isize + offset.1) as usize);
+                if neighbour.0 >= W || neighbour.1 >= H { continue; }
+
+                let index = neighbour.0 * H + neighbour.1;
+                if self.stamps[index] == self.epoch { continue; }
+
+                if let ColourType::Colour(colour) = board[neighbour.0][neighbour.1] {
+                    if colour == code {
+                        self.stamps[index] = self.epoch;
+                        self.open_set.push(neighbour);
+                    }
+                }
+            }
+        }
+
+        let cleared = match rule.condition {
+            ClearCondition::WallToWall => touches_left && touches_right,
+            ClearCondition::MinimumSize(size) => self.region.len() >= size,
+            // Rows are checked above
+            ClearCondition::FullRows => false
+        };
+        if cleared {
+            clears.push((code, self.region.clone()));
+        }
+        else if self.track_regions {
+            self.regions.push((code, self.region.clone()));
+        }
+    }
+}
+
+pub(super) fn neighbour_offsets(connectivity: Connectivity) -> &'static [(isize, isize)] {
+    match connectivity {
+        Connectivity::Four => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+        Connectivity::Eight => &[(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use super::*;
+
+    const W: usize = 4;
+    const H: usize = 4;
+
+    fn board(cells: &[(usize, usize, u8)]) -> [[ColourType; H]; W] {
+        let mut board = [[ColourType::Empty; H]; W];
+        for &(x, y, code) in cells {
+            board[x][y] = ColourType::Colour(ColourCode(code));
+        }
+        board
+    }
+
+    fn cleared(board: &[[ColourType; H]; W], rule: ClearRule) -> Vec<usize> {
+        let mut detector = ClearDetector::new();
+        detector.mark_all();
+        detector.find_clears(board, &rule).iter().map(|(_, region)| region.len()).collect()
+    }
+
+    fn rule(condition: ClearCondition, connectivity: Connectivity) -> ClearRule {
+        ClearRule { condition, connectivity }
+    }
+
+    #[test]
+    fn parses_every_condition_and_connectivity() {
+        assert_eq!(ClearRule::parse("wall_to_wall"), Ok(ClearRule::default()));
+        assert_eq!(ClearRule::parse(" full_rows / 8 "), Ok(rule(ClearCondition::FullRows, Connectivity::Eight)));
+        assert_eq!(ClearRule::parse("min_size"), Ok(rule(ClearCondition::MinimumSize(DEFAULT_MINIMUM_SIZE), Connectivity::Four)));
+        assert_eq!(ClearRule::parse("min_size:250/4"), Ok(rule(ClearCondition::MinimumSize(250), Connectivity::Four)));
+
+        assert!(ClearRule::parse("diagonal").is_err());
+        assert!(ClearRule::parse("min_size:lots").is_err());
+        assert!(ClearRule::parse("wall_to_wall/6").is_err());
+    }
+
+    #[test]
+    fn wall_to_wall_follows_diagonals_only_with_eight_connectivity() {
+        let staircase = board(&[(0, 0, 1), (1, 1, 1), (2, 2, 1), (3, 3, 1)]);
+        assert!(cleared(&staircase, rule(ClearCondition::WallToWall, Connectivity::Four)).is_empty());
+        assert_eq!(cleared(&staircase, rule(ClearCondition::WallToWall, Connectivity::Eight)), vec![4]);
+
+        let short = board(&[(0, 0, 1), (1, 0, 1), (2, 0, 1), (3, 0, 2)]);
+        assert!(cleared(&short, ClearRule::default()).is_empty());
+    }
+
+    #[test]
+    fn minimum_size_clears_large_enough_regions_anywhere() {
+        let square = board(&[(1, 1, 1), (2, 1, 1), (1, 2, 1), (2, 2, 1)]);
+        assert_eq!(cleared(&square, rule(ClearCondition::MinimumSize(4), Connectivity::Four)), vec![4]);
+        assert!(cleared(&square, rule(ClearCondition::MinimumSize(5), Connectivity::Four)).is_empty());
+    }
+
+    #[test]
+    fn full_rows_clear_any_mix_of_colours() {
+        let rows = board(&[(0, 0, 1), (1, 0, 2), (2, 0, 3), (3, 0, 1), (0, 1, 1), (1, 1, 1), (2, 1, 1)]);
+        let clears = {
+            let mut detector = ClearDetector::new();
+            detector.mark_all();
+            detector.find_clears(&rows, &rule(ClearCondition::FullRows, Connectivity::Four))
+        };
+
+        assert_eq!(clears.len(), 1);
+        assert_eq!(clears[0].1, vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn only_searches_from_marked_cells() {
+        let row = board(&[(0, 0, 1), (1, 0, 1), (2, 0, 1), (3, 0, 1)]);
+        let mut detector = ClearDetector::new();
+        assert!(detector.find_clears(&row, &ClearRule::default()).is_empty());
+
+        detector.mark((2, 0));
+        assert_eq!(detector.find_clears(&row, &ClearRule::default()).len(), 1);
+    }
+
+    #[test]
+    fn tracks_the_regions_that_didnt_clear() {
+        let rows = board(&[(0, 0, 1), (1, 0, 2), (2, 0, 2), (3, 0, 1), (0, 1, 1), (1, 1, 1)]);
+        let mut detector = ClearDetector::new();
+        detector.set_track_regions(true);
+        detector.mark_all();
+
+        assert_eq!(detector.find_clears(&rows, &rule(ClearCondition::FullRows, Connectivity::Four)).len(), 1);
+        assert!(detector.scanned_all());
+        let mut sizes: Vec<usize> = detector.take_regions().iter().map(|(_, region)| region.len()).collect();
+        sizes.sort_unstable();
+        assert_eq!(sizes, vec![1, 2, 3]);
+
+        detector.mark((3, 0));
+        detector.find_clears(&rows, &rule(ClearCondition::FullRows, Connectivity::Four));
+        assert!(!detector.scanned_all());
+        assert_eq!(detector.take_regions().len(), 1);
+    }
+
+    /// Wall to wall regions found the simple way, by flooding from every pixel in the left column
+    fn reference_spans<const W: usize, const H: usize>(board: &[[ColourType; H]; W]) -> HashSet<Vec<(usize, usize)>> {
+        let mut visited = HashSet::new();
+        let mut spans = HashSet::new();
+        for y in 0..H {
+            let code = match board[0][y] {
+                ColourType::Colour(code) if !visited.contains(&(0, y)) => code,
+                _ => continue
+            };
+
+            let mut region = vec![(0, y)];
+            let mut open_set: Vec<(usize, usize)> = vec![(0, y)];
+            visited.insert((0, y));
+            while let Some((x, y)) = open_set.pop() {
+                for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+                    if nx < W && ny < H && board[nx][ny] == ColourType::Colour(code) && visited.insert((nx, ny)) {
+                        region.push((nx, ny));
+                        open_set.push((nx, ny));
+                    }
+                }
+            }
+            if region.iter().any(|&(x, _)| x == W - 1) {
+                region.sort_unstable();
+                spans.insert(region);
+            }
+        }
+        spans
+    }
+
+    /// The detector's full scan and the simple search agree on boards of random squares, and after a change
+    /// the detector finds the new span from the changed pixels alone
+    #[test]
+    fn matches_the_simple_search() {
+        const SIZE: usize = 60;
+        const SQUARE: usize = 4;
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..20 {
+            let mut board = [[ColourType::Empty; SIZE]; SIZE];
+            let codes: Vec<Vec<u8>> = (0..SIZE / SQUARE).map(|_| (0..SIZE / SQUARE).map(|_| rng.gen_range(0..3)).collect()).collect();
+            for (x, column) in board.iter_mut().enumerate() {
+                for (y, cell) in column.iter_mut().enumerate() {
+                    *cell = ColourType::Colour(ColourCode(codes[x / SQUARE][y / SQUARE]));
+                }
+            }
+
+            let mut detector = ClearDetector::new();
+            let found = |clears: Vec<Clear>| -> HashSet<Vec<(usize, usize)>> {
+                clears.into_iter().map(|(_, mut region)| { region.sort_unstable(); region }).collect()
+            };
+            detector.mark_all();
+            assert_eq!(found(detector.find_clears(&board, &ClearRule::default())), reference_spans(&board));
+
+            // Paint a new row across the middle, marking only the changed pixels
+            let y = rng.gen_range(0..SIZE);
+            for (x, column) in board.iter_mut().enumerate() {
+                column[y] = ColourType::Colour(ColourCode(3));
+                detector.mark((x, y));
+            }
+            let changed_spans = reference_spans(&board).into_iter().filter(|region| region.contains(&(0, y))).collect();
+            assert_eq!(found(detector.find_clears(&board, &ClearRule::default())), changed_spans);
+        }
+    }
+}
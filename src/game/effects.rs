@@ -0,0 +1,139 @@
+use rand::Rng;
+use rand::rngs::ThreadRng;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+const SWEEP_FRAMES: u32 = 40;
+const SWEEP_BAND: usize = 24;
+const SWEEP_ALPHA: u8 = 180;
+const MAX_PARTICLES_PER_CLEAR: usize = 150;
+const PARTICLE_LIFE: (u32, u32) = (30, 60);
+const PARTICLE_GRAVITY: f32 = 0.15;
+const PIXELS_PER_SHAKE: f32 = 500.0;
+const MAX_SHAKE: f32 = 8.0;
+const SHAKE_DECAY: f32 = 0.85;
+
+/// A bright band travelling from one wall to the other across a cleared region
+struct Sweep {
+    /// Sorted by x
+    pixels: Vec<(usize, usize)>,
+    width: usize,
+    age: u32,
+}
+
+struct Particle {
+    position: (f32, f32),
+    velocity: (f32, f32),
+    colour: Color,
+    life: u32,
+}
+
+/// Purely cosmetic clear effects. These never touch the board or the game's random numbers
+pub struct Effects {
+    sweeps: Vec<Sweep>,
+    particles: Vec<Particle>,
+    shake: f32,
+    shake_offset: (i32, i32),
+    pub screen_shake: bool,
+    rng: ThreadRng,
+}
+
+impl Effects {
+    pub fn new() -> Self {
+        Self {
+            sweeps: Vec::new(),
+            particles: Vec::new(),
+            shake: 0.0,
+            shake_offset: (0, 0),
+            screen_shake: true,
+            rng: rand::thread_rng()
+        }
+    }
+
+    /// Starts the effects for a region that just started flashing on a board `width` pixels wide
+    pub fn start_clear(&mut self, mut region: Vec<(usize, usize)>, colour: Color, width: usize) {
+        let sample_step = (region.len() / MAX_PARTICLES_PER_CLEAR).max(1);
+        for &(x, y) in region.iter().step_by(sample_step) {
+            self.particles.push(Particle {
+                position: (x as f32, y as f32),
+                velocity: (self.rng.gen_range(-2.0..2.0), self.rng.gen_range(0.5..4.0)),
+                colour,
+                life: self.rng.gen_range(PARTICLE_LIFE.0..PARTICLE_LIFE.1)
+            });
+        }
+
+        if self.screen_shake {
+            self.shake = (self.shake + region.len() as f32 / PIXELS_PER_SHAKE).min(MAX_SHAKE);
+        }
+
+        region.sort_unstable_by_key(|pixel| pixel.0);
+        self.sweeps.push(Sweep { pixels: region, width, age: 0 });
+    }
+
+    pub fn shake_offset(&self) -> (i32, i32) {
+        self.shake_offset
+    }
+
+    pub fn update(&mut self) {
+        for sweep in &mut self.sweeps {
+            sweep.age += 1;
+        }
+        self.sweeps.retain(|sweep| sweep.age < SWEEP_FRAMES);
+
+        for particle in &mut self.particles {
+            particle.position.0 += particle.velocity.0;
+            particle.position.1 += particle.velocity.1;
+            particle.velocity.1 -= PARTICLE_GRAVITY;
+            particle.life -= 1;
+        }
+        self.particles.retain(|particle| particle.life > 0);
+
+        self.shake *= SHAKE_DECAY;
+        if self.shake < 0.5 {
+            self.shake = 0.0;
+            self.shake_offset = (0, 0);
+        }
+        else {
+            self.shake_offset = (
+                self.rng.gen_range(-self.shake..=self.shake) as i32,
+                self.rng.gen_range(-self.shake..=self.shake) as i32
+            );
+        }
+    }
+
+    /// Draws the effects over a board of height `board_height` drawn in `board`
+    pub fn draw(&self, canvas: &mut Canvas<Window>, board: Rect, pixel_size: u32, board_height: usize) {
+        let to_screen = |x: f32, y: f32| (
+            board.left() + (x * pixel_size as f32) as i32,
+            board.top() + ((board_height as f32 - y - 1.0) * pixel_size as f32) as i32
+        );
+
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        for sweep in &self.sweeps {
+            let front = sweep.age as usize * (sweep.width + SWEEP_BAND) / SWEEP_FRAMES as usize;
+            let start = sweep.pixels.partition_point(|pixel| pixel.0 + SWEEP_BAND < front);
+            let end = sweep.pixels.partition_point(|pixel| pixel.0 <= front);
+
+            let rects: Vec<Rect> = sweep.pixels[start..end].iter().map(|&(x, y)| {
+                let position = to_screen(x as f32, y as f32);
+                Rect::new(position.0, position.1, pixel_size, pixel_size)
+            }).collect();
+
+            if rects.is_empty() { continue; }
+            canvas.set_draw_color(Color::RGBA(255, 255, 255, SWEEP_ALPHA));
+            canvas.fill_rects(&rects).unwrap();
+        }
+
+        for particle in &self.particles {
+            let alpha = (255 * particle.life / PARTICLE_LIFE.1).min(255) as u8;
+            let position = to_screen(particle.position.0, particle.position.1);
+            canvas.set_draw_color(Color::RGBA(particle.colour.r, particle.colour.g, particle.colour.b, alpha));
+            canvas.fill_rect(Rect::new(position.0, position.1, pixel_size * 2, pixel_size * 2)).unwrap();
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+    }
+}
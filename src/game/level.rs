@@ -0,0 +1,39 @@
+pub const MAX_LEVEL: u32 = 20;
+const CLEARS_PER_LEVEL: u32 = 5;
+const SQUARE_ROWS_PER_LEVEL: u64 = 2;
+
+const FRAMES_PER_DROP: [u64; 15] = [30, 27, 24, 21, 18, 16, 14, 12, 10, 8, 6, 5, 4, 3, 2];
+
+#[derive(Copy, Clone, Debug)]
+pub struct Gravity {
+    pub frames_per_drop: u64,
+    pub drop_amount: u32,
+}
+
+impl Gravity {
+    pub fn for_level(level: u32, square_pixel_width: u32) -> Self {
+        match FRAMES_PER_DROP.get(level as usize) {
+            Some(frames) => Gravity { frames_per_drop: *frames, drop_amount: square_pixel_width },
+            // Past the end of the table drop every frame and fall further each level
+            None => Gravity {
+                frames_per_drop: 1,
+                drop_amount: square_pixel_width * (level - FRAMES_PER_DROP.len() as u32 + 2) / 2
+            }
+        }
+    }
+}
+
+pub fn spawn_delay(level: u32) -> u64 {
+    30u64.saturating_sub(level as u64).max(10)
+}
+
+/// Level reached after `clears` clears removing `pixels_cleared` pixels in total
+///
+/// # Arguments
+/// * `pixels_per_square_row` - Pixels in one full row of squares, used to weigh large clears
+pub fn level_for(clears: u32, pixels_cleared: u64, pixels_per_square_row: u64) -> u32 {
+    let square_rows = pixels_cleared / pixels_per_square_row;
+    (clears / CLEARS_PER_LEVEL)
+        .max((square_rows / SQUARE_ROWS_PER_LEVEL) as u32)
+        .min(MAX_LEVEL)
+}
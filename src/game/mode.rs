@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+use std::time::Duration;
+
+/// Game updates per second, which the in-game timer counts in
+pub const TICKS_PER_SECOND: u64 = 60;
+const SPRINT_CLEARS: u32 = 40;
+const ULTRA_TICKS: u64 = 2 * 60 * TICKS_PER_SECOND;
+/// Zen gravity stops speeding up past this level
+const ZEN_MAX_GRAVITY_LEVEL: u32 = 6;
+
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_nanos(ticks * 1_000_000_000 / TICKS_PER_SECOND)
+}
+
+/// What finishes a game besides topping out
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EndCondition {
+    None,
+    /// Finished after this many clears
+    Clears(u32),
+    /// Finished once the timer reaches this many ticks
+    Ticks(u64),
+}
+
+/// How games of a mode are compared on the high score table
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Ranking {
+    /// Highest score first
+    Score,
+    /// Fastest finish first. Only finished games are ranked
+    Time,
+}
+
+/// What happens when the stack reaches the top of the board
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TopOut {
+    GameOver,
+    /// Clear the top of the stack and carry on
+    Overflow,
+    Ignore,
+}
+
+/// Rules a game is played under
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum GameMode {
+    /// Play until topping out, for the highest score
+    Endless,
+    /// Authored boards with a fixed set of pieces and a goal
+    Puzzle,
+    /// Make 40 clears as fast as possible
+    Sprint,
+    /// Score as much as possible in two minutes
+    Ultra,
+    /// No game over. Topping out clears the top of the stack instead, and gravity stays gentle
+    Zen,
+    /// Paint sand and drop pieces by hand to experiment with the physics
+    Sandbox,
+}
+
+impl GameMode {
+    /// New modes go on the end, as saves store the index
+    pub const ALL: [GameMode; 6] = [
+        GameMode::Endless, GameMode::Puzzle, GameMode::Sprint, GameMode::Ultra, GameMode::Zen, GameMode::Sandbox
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::Puzzle => "Puzzle",
+            GameMode::Sprint => "Sprint",
+            GameMode::Ultra => "Ultra",
+            GameMode::Zen => "Zen",
+            GameMode::Sandbox => "Sandbox",
+        }
+    }
+
+    /// Mode with this name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether games of this mode are ranked in the high score table
+    pub fn has_high_scores(&self) -> bool {
+        match self {
+            GameMode::Endless | GameMode::Sprint | GameMode::Ultra => true,
+            GameMode::Puzzle | GameMode::Zen | GameMode::Sandbox => false,
+        }
+    }
+
+    pub fn with_high_scores() -> impl Iterator<Item = GameMode> {
+        Self::ALL.into_iter().filter(GameMode::has_high_scores)
+    }
+
+    /// Puzzles end through their own goals, see `Puzzle`
+    pub fn end_condition(&self) -> EndCondition {
+        match self {
+            GameMode::Endless | GameMode::Puzzle | GameMode::Zen | GameMode::Sandbox => EndCondition::None,
+            GameMode::Sprint => EndCondition::Clears(SPRINT_CLEARS),
+            GameMode::Ultra => EndCondition::Ticks(ULTRA_TICKS),
+        }
+    }
+
+    pub fn ranking(&self) -> Ranking {
+        match self {
+            GameMode::Sprint => Ranking::Time,
+            GameMode::Endless | GameMode::Puzzle | GameMode::Ultra | GameMode::Zen | GameMode::Sandbox => Ranking::Score,
+        }
+    }
+
+    pub fn top_out(&self) -> TopOut {
+        match self {
+            GameMode::Zen => TopOut::Overflow,
+            GameMode::Sandbox => TopOut::Ignore,
+            _ => TopOut::GameOver
+        }
+    }
+
+    /// Whether new pieces spawn on their own. In the sandbox they're only dropped by hand
+    pub fn spawns_pieces(&self) -> bool {
+        *self != GameMode::Sandbox
+    }
+
+    /// Level whose gravity is used, which can be gentler than the level reached
+    pub fn gravity_level(&self, level: u32) -> u32 {
+        match self {
+            GameMode::Zen => level.min(ZEN_MAX_GRAVITY_LEVEL),
+            _ => level
+        }
+    }
+
+    /// Orders results of this mode best first, given as `(score, duration)`
+    pub fn compare(&self, a: (u64, Duration), b: (u64, Duration)) -> Ordering {
+        match self.ranking() {
+            Ranking::Score => b.0.cmp(&a.0),
+            Ranking::Time => a.1.cmp(&b.1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_modes_rank_the_highest_score_first() {
+        let slow_high = (500, Duration::from_secs(90));
+        let fast_low = (100, Duration::from_secs(30));
+        assert_eq!(GameMode::Endless.compare(slow_high, fast_low), Ordering::Less);
+        assert_eq!(GameMode::Ultra.compare(fast_low, slow_high), Ordering::Greater);
+    }
+
+    #[test]
+    fn sprint_ranks_the_fastest_time_first() {
+        let slow_high = (500, Duration::from_secs(90));
+        let fast_low = (100, Duration::from_secs(30));
+        assert_eq!(GameMode::Sprint.compare(fast_low, slow_high), Ordering::Less);
+        assert_eq!(GameMode::Sprint.compare(fast_low, (900, Duration::from_secs(30))), Ordering::Equal);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for mode in GameMode::ALL {
+            assert_eq!(GameMode::from_name(&mode.name().to_uppercase()), Some(mode));
+        }
+        assert_eq!(GameMode::from_name("marathon"), None);
+    }
+}
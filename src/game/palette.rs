@@ -0,0 +1,115 @@
+use sdl2::pixels::Color;
+
+/// Number of colours every palette has to provide
+pub const MAX_COLOURS: usize = 8;
+const PATTERN_PERIOD: usize = 6;
+const PATTERN_SHADE: f32 = 0.55;
+
+#[derive(Clone, Debug)]
+pub struct Palette {
+    pub name: String,
+    colours: Vec<Color>,
+    /// Overlay a different pattern on each colour so they can be told apart without hue
+    pub patterns: bool,
+}
+
+impl Palette {
+    pub const BUILT_IN: [&'static str; 3] = ["classic", "red_green", "tritanopia"];
+
+    pub fn new(name: &str, colours: Vec<Color>) -> Result<Self, String> {
+        if colours.len() != MAX_COLOURS {
+            return Err(format!("Palette '{}' has {} colours, expected {}", name, colours.len(), MAX_COLOURS));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            colours,
+            patterns: false
+        })
+    }
+
+    pub fn built_in(name: &str) -> Option<Self> {
+        let colours = match name {
+            "classic" => vec![
+                Color::RED, Color::GREEN, Color::BLUE, Color::RGB(195, 0, 255),
+                Color::RGB(255, 220, 0), Color::RGB(0, 220, 220), Color::RGB(255, 128, 0), Color::RGB(255, 105, 180),
+            ],
+            // Okabe-Ito colours, distinguishable with reduced red or green sensitivity (protanopia and deuteranopia)
+            "red_green" => vec![
+                Color::RGB(230, 159, 0), Color::RGB(86, 180, 233), Color::RGB(0, 158, 115), Color::RGB(204, 121, 167),
+                Color::RGB(240, 228, 66), Color::RGB(0, 114, 178), Color::RGB(213, 94, 0), Color::RGB(220, 220, 220),
+            ],
+            // Avoids blue / yellow pairs, which are confused with reduced blue sensitivity
+            "tritanopia" => vec![
+                Color::RGB(220, 38, 127), Color::RGB(0, 153, 153), Color::RGB(240, 240, 240), Color::RGB(254, 97, 0),
+                Color::RGB(100, 143, 255), Color::RGB(120, 60, 40), Color::RGB(160, 240, 200), Color::RGB(128, 128, 128),
+            ],
+            _ => return None
+        };
+
+        Some(Self::new(name, colours).unwrap())
+    }
+
+    /// The built-in palette `step` places after this one, keeping the pattern setting
+    pub fn cycle_built_in(&self, step: i32) -> Self {
+        let count = Self::BUILT_IN.len() as i32;
+        let next = match Self::BUILT_IN.iter().position(|name| *name == self.name) {
+            Some(current) => (current as i32 + step).rem_euclid(count),
+            None => 0
+        };
+        let mut palette = Self::built_in(Self::BUILT_IN[next as usize]).unwrap();
+        palette.patterns = self.patterns;
+        palette
+    }
+
+    /// Parses space separated `r,g,b` colours
+    pub fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let colours = source.split_whitespace().map(|colour| -> Result<Color, String> {
+            let channels = colour.split(',').map(|c| c.trim().parse::<u8>()).collect::<Result<Vec<u8>, _>>()
+                .map_err(|e| format!("Invalid colour '{}' in palette '{}': {}", colour, name, e))?;
+            match channels[..] {
+                [r, g, b] => Ok(Color::RGB(r, g, b)),
+                _ => Err(format!("Invalid colour '{}' in palette '{}'", colour, name))
+            }
+        }).collect::<Result<Vec<Color>, String>>()?;
+
+        Self::new(name, colours)
+    }
+
+    pub fn colour(&self, index: usize) -> Color {
+        self.colours[index]
+    }
+
+    /// Darkens `colour` where the pattern for colour `index` is set at board position `(x, y)`
+    pub fn apply_pattern(&self, colour: Color, index: usize, x: usize, y: usize) -> Color {
+        if !self.patterns || !pattern_set(index, x, y) { return colour; }
+
+        Color::RGB(
+            (colour.r as f32 * PATTERN_SHADE) as u8,
+            (colour.g as f32 * PATTERN_SHADE) as u8,
+            (colour.b as f32 * PATTERN_SHADE) as u8
+        )
+    }
+}
+
+fn pattern_set(index: usize, x: usize, y: usize) -> bool {
+    let (px, py) = (x % PATTERN_PERIOD, y % PATTERN_PERIOD);
+    match index % MAX_COLOURS {
+        // Solid
+        0 => false,
+        // Horizontal stripes
+        1 => py < 2,
+        // Vertical stripes
+        2 => px < 2,
+        // Diagonal stripes
+        3 => (px + py) % PATTERN_PERIOD < 2,
+        // Checkerboard
+        4 => (px < PATTERN_PERIOD / 2) != (py < PATTERN_PERIOD / 2),
+        // Dots
+        5 => px < 2 && py < 2,
+        // Grid
+        6 => px < 2 || py < 2,
+        // Anti-diagonal stripes
+        _ => (px + PATTERN_PERIOD - py) % PATTERN_PERIOD < 2,
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::col_println;
+use crate::game::ColourCode;
+use crate::game::{Game, snapshot};
+use crate::game::palette::{MAX_COLOURS, Palette};
+use crate::game::tetromino::TetrominoShape;
+
+pub const PUZZLE_DIR: &str = "static/puzzles";
+/// Puzzle boards are drawn in this palette, so pieces match the sand whichever palette the player has chosen
+const BOARD_PALETTE: &str = "classic";
+
+pub fn board_palette() -> Palette {
+    Palette::built_in(BOARD_PALETTE).unwrap()
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Goal {
+    /// Clear at least this many pixels in total
+    ClearPixels(u64),
+    /// Remove every pixel of one colour from the board
+    ClearColour(ColourCode),
+    /// Place this many pieces without topping out
+    SurvivePieces(u32),
+}
+
+impl Goal {
+    fn parse(source: &str) -> Result<Self, String> {
+        let (kind, amount) = source.split_once(' ').ok_or(format!("Invalid goal '{}'", source))?;
+        let amount = amount.trim().parse::<u64>().map_err(|_| format!("Invalid number in goal '{}'", source))?;
+        match kind {
+            "clear_pixels" => Ok(Goal::ClearPixels(amount)),
+            "clear_colour" if (amount as usize) < MAX_COLOURS => Ok(Goal::ClearColour(ColourCode(amount as u8))),
+            "survive" => Ok(Goal::SurvivePieces(amount as u32)),
+            _ => Err(format!("Invalid goal '{}'", source))
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Goal::ClearPixels(pixels) => format!("Clear {} pixels", pixels),
+            Goal::ClearColour(code) => format!("Clear every pixel of colour {}", code.0 + 1),
+            Goal::SurvivePieces(pieces) => format!("Place {} pieces", pieces),
+        }
+    }
+
+    /// Short form of the goal that fits beside the board
+    pub fn summary(&self) -> String {
+        match self {
+            Goal::ClearPixels(pixels) => format!("{} px", pixels),
+            Goal::ClearColour(code) => format!("Colour {}", code.0 + 1),
+            Goal::SurvivePieces(pieces) => format!("{} pieces", pieces),
+        }
+    }
+}
+
+/// An authored level, read from a text file of `key = value` lines:
+///
+/// - `name`: shown in the level select
+/// - `board`: PNG of the starting board in the classic palette, such as one saved in the sandbox, relative to the level file.
+///   See `snapshot::import_board`
+/// - `pieces`: space separated `<shape>:<colour>` pieces dropped in order, such as `L:0 I:2`
+/// - `goal`: `clear_pixels <n>`, `clear_colour <colour>` or `survive <pieces>`
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    pub name: String,
+    pub path: PathBuf,
+    pub board: Option<PathBuf>,
+    pub(super) pieces: Vec<(TetrominoShape, ColourCode)>,
+    pub goal: Goal,
+}
+
+impl Puzzle {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut name = None;
+        let mut board = None;
+        let mut pieces = Vec::new();
+        let mut goal = None;
+
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let (key, value) = line.split_once('=').ok_or(format!("Invalid line '{}'", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = Some(value.to_string()),
+                "board" => board = Some(path.parent().unwrap_or(Path::new("")).join(value)),
+                "pieces" => {
+                    for piece in value.split_whitespace() {
+                        let (shape, colour) = piece.split_once(':').ok_or(format!("Invalid piece '{}'", piece))?;
+                        let shape = TetrominoShape::from_name(shape).ok_or(format!("Unknown shape '{}'", shape))?;
+                        let colour = colour.parse::<u8>().ok().filter(|c| (*c as usize) < MAX_COLOURS)
+                            .ok_or(format!("Invalid colour in piece '{}'", piece))?;
+                        pieces.push((shape, ColourCode(colour)));
+                    }
+                }
+                "goal" => goal = Some(Goal::parse(value)?),
+                key => return Err(format!("Unknown key '{}'", key))
+            }
+        }
+
+        if pieces.is_empty() {
+            return Err("Puzzle has no pieces".to_string());
+        }
+
+        Ok(Self {
+            name: name.ok_or("Puzzle has no name")?,
+            path: path.to_path_buf(),
+            board,
+            pieces,
+            goal: goal.ok_or("Puzzle has no goal")?
+        })
+    }
+
+    /// Every puzzle in `directory` in file name order, skipping any that fail to load
+    pub fn load_all(directory: &Path) -> Vec<Self> {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(directory) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map_or(false, |extension| extension == "txt"))
+                .collect(),
+            Err(e) => {
+                col_println!((yellow, bold), "Failed to read puzzles from {}: {}", directory.display(), e);
+                return Vec::new();
+            }
+        };
+        paths.sort();
+
+        paths.iter().filter_map(|path| match Self::load(path) {
+            Ok(puzzle) => Some(puzzle),
+            Err(e) => {
+                col_println!((yellow, bold), "Skipping puzzle {}: {}", path.display(), e);
+                None
+            }
+        }).collect()
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len()
+    }
+}
+
+impl<const W: usize, const H: usize> Game<W, H> {
+    /// Exports the board in the puzzle palette, ready to be used as a puzzle's `board`
+    pub fn export_puzzle_board(&self, path: &Path) -> Result<(), String> {
+        snapshot::export_board(&self.board, &board_palette(), path)
+    }
+}
+
+/// A puzzle being played
+#[derive(Clone, Debug)]
+pub(super) struct PuzzleState {
+    pub(super) puzzle: Puzzle,
+    pub(super) next_piece: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use crate::util::temporary_path;
+    use super::*;
+
+    /// Writes `source` to a puzzle file in a temporary directory and loads it
+    fn load(name: &str, source: &str) -> Result<Puzzle, String> {
+        let path = temporary_path(&format!("{}.txt", name));
+        fs::write(&path, source).unwrap();
+        let puzzle = Puzzle::load(&path);
+        fs::remove_file(&path).unwrap();
+        puzzle
+    }
+
+    #[test]
+    fn loads_every_key() {
+        let puzzle = load("every-key", "# A comment\n\nname = Stairs \nboard = stairs.png\npieces = L:0  I:3 o:1\ngoal = clear_colour 2\n").unwrap();
+
+        assert_eq!(puzzle.name, "Stairs");
+        assert_eq!(puzzle.board, Some(env::temp_dir().join("stairs.png")));
+        assert_eq!(puzzle.pieces, vec![
+            (TetrominoShape::LShape, ColourCode(0)), (TetrominoShape::Line, ColourCode(3)), (TetrominoShape::Square, ColourCode(1))
+        ]);
+        assert_eq!(puzzle.goal, Goal::ClearColour(ColourCode(2)));
+    }
+
+    #[test]
+    fn board_is_optional() {
+        let puzzle = load("no-board", "name = Empty\npieces = S:1\ngoal = survive 1").unwrap();
+        assert_eq!(puzzle.board, None);
+        assert_eq!(puzzle.goal, Goal::SurvivePieces(1));
+    }
+
+    #[test]
+    fn rejects_invalid_puzzles() {
+        let invalid = [
+            ("no-name", "pieces = I:0\ngoal = survive 1"),
+            ("no-goal", "name = A\npieces = I:0"),
+            ("no-pieces", "name = A\ngoal = survive 1"),
+            ("unknown-key", "name = A\npieces = I:0\ngoal = survive 1\nspeed = 2"),
+            ("no-equals", "name = A\npieces I:0\ngoal = survive 1"),
+            ("unknown-shape", "name = A\npieces = Q:0\ngoal = survive 1"),
+            ("bad-colour", &format!("name = A\npieces = I:{}\ngoal = survive 1", MAX_COLOURS)),
+            ("bad-goal", "name = A\npieces = I:0\ngoal = clear_everything 1"),
+            ("bad-goal-colour", &format!("name = A\npieces = I:0\ngoal = clear_colour {}", MAX_COLOURS)),
+        ];
+
+        for (name, source) in invalid {
+            assert!(load(name, source).is_err(), "{} loaded", name);
+        }
+    }
+
+    #[test]
+    fn shipped_puzzles_all_load() {
+        let files = fs::read_dir(PUZZLE_DIR).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().map_or(false, |extension| extension == "txt"))
+            .count();
+        assert_eq!(Puzzle::load_all(Path::new(PUZZLE_DIR)).len(), files);
+    }
+}
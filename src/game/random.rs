@@ -0,0 +1,50 @@
+use rand::{Error, RngCore};
+
+/// Small seedable generator for everything that affects play, so a game can be replayed from its seed
+/// and its state saved as a single number. Uses xorshift64*
+#[derive(Copy, Clone, Debug)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift never leaves, so mix the seed to avoid it
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Self { state: if state == 0 { 1 } else { state } }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Restores a generator from `state`
+    pub fn from_state(state: u64) -> Self {
+        Self { state: if state == 0 { 1 } else { state } }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
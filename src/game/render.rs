@@ -0,0 +1,194 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::surface::Surface;
+use sdl2::video::Window;
+use crate::game::Game;
+
+const BYTES_PER_PIXEL: usize = 3;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RenderBackend {
+    /// Streaming texture updated in place and scaled up by the renderer
+    Texture,
+    /// Runs of same coloured pixels batched into `fill_rects` calls
+    Rects,
+    /// Board scaled up on the CPU into a surface that is blitted every frame
+    Software,
+}
+
+impl RenderBackend {
+    pub fn next(&self) -> Self {
+        match self {
+            RenderBackend::Texture => RenderBackend::Rects,
+            RenderBackend::Rects => RenderBackend::Software,
+            RenderBackend::Software => RenderBackend::Texture,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "texture" => Some(RenderBackend::Texture),
+            "rects" => Some(RenderBackend::Rects),
+            "software" => Some(RenderBackend::Software),
+            _ => None
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> Game<W, H> {
+    /// Writes one row of the board as seen on screen (top row first) into `row` as RGB24
+    fn fill_screen_row(&self, screen_y: usize, row: &mut [u8]) {
+        let y = H - screen_y - 1;
+        for x in 0..W {
+            let colour = self.pixel_colour(x, y).rgb();
+            row[x * BYTES_PER_PIXEL] = colour.0;
+            row[x * BYTES_PER_PIXEL + 1] = colour.1;
+            row[x * BYTES_PER_PIXEL + 2] = colour.2;
+        }
+    }
+
+    /// The board as seen on screen as RGB24, top row first with one pixel per cell.
+    /// Reuses the frame the texture backend has already assembled instead of building another
+    pub fn board_frame(&self) -> Cow<'_, [u8]> {
+        let pitch = W * BYTES_PER_PIXEL;
+        if self.render_backend == RenderBackend::Texture && self.frame.len() == pitch * H {
+            return Cow::Borrowed(&self.frame);
+        }
+
+        let mut frame = vec![0; pitch * H];
+        for (screen_y, row) in frame.chunks_exact_mut(pitch).enumerate() {
+            self.fill_screen_row(screen_y, row);
+        }
+        Cow::Owned(frame)
+    }
+
+    /// Copies the board into the streaming texture, uploading only rows that changed since the last frame,
+    /// and lets the renderer scale it up to `pixel_size`
+    pub(super) fn draw_texture(&mut self, canvas: &mut Canvas<Window>) {
+        let pitch = W * BYTES_PER_PIXEL;
+        let destination = self.draw_rect();
+
+        if self.texture.is_none() {
+            self.texture = Some(
+                canvas.texture_creator().create_texture_streaming(PixelFormatEnum::RGB24, W as u32, H as u32).unwrap()
+            );
+            self.frame.clear();
+        }
+        let full_upload = self.frame.len() != pitch * H;
+        if full_upload {
+            self.frame = vec![0; pitch * H];
+        }
+
+        let mut row = vec![0; pitch];
+        let mut dirty_start = None;
+
+        for screen_y in 0..=H {
+            let dirty = screen_y < H && {
+                self.fill_screen_row(screen_y, &mut row);
+
+                let frame_row = &mut self.frame[screen_y * pitch..(screen_y + 1) * pitch];
+                let changed = full_upload || *frame_row != row[..];
+                if changed { frame_row.copy_from_slice(&row); }
+                changed
+            };
+
+            // Upload each run of consecutive dirty rows in one go
+            match (dirty, dirty_start) {
+                (true, None) => dirty_start = Some(screen_y),
+                (false, Some(start)) => {
+                    self.texture.as_mut().unwrap().update(
+                        Rect::new(0, start as i32, W as u32, (screen_y - start) as u32),
+                        &self.frame[start * pitch..screen_y * pitch],
+                        pitch
+                    ).unwrap();
+                    dirty_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        canvas.copy(self.texture.as_ref().unwrap(), None, destination).unwrap();
+    }
+
+    /// Fills the background once then draws each horizontal run of a colour as a single rect
+    pub(super) fn draw_rects(&self, canvas: &mut Canvas<Window>) {
+        let board = self.draw_rect();
+        let pixel_size = self.pixel_size;
+
+        canvas.set_draw_color(self.background);
+        canvas.fill_rect(board).unwrap();
+
+        let mut runs: HashMap<Color, Vec<Rect>> = HashMap::new();
+        for screen_y in 0..H {
+            let y = H - screen_y - 1;
+            let mut x = 0;
+            while x < W {
+                let colour = self.pixel_colour(x, y);
+                let start = x;
+                while x < W && self.pixel_colour(x, y) == colour {
+                    x += 1;
+                }
+                if colour == self.background { continue; }
+
+                runs.entry(colour).or_default().push(Rect::new(
+                    board.left() + (start as u32 * pixel_size) as i32, board.top() + (screen_y as u32 * pixel_size) as i32,
+                    (x - start) as u32 * pixel_size, pixel_size
+                ));
+            }
+        }
+
+        for (colour, rects) in runs {
+            canvas.set_draw_color(colour);
+            canvas.fill_rects(&rects).unwrap();
+        }
+    }
+
+    /// Scales the board up to `pixel_size` on the CPU and blits it through a temporary texture
+    pub(super) fn draw_software(&self, canvas: &mut Canvas<Window>) {
+        let pixel_size = self.pixel_size as usize;
+        let pitch = W * pixel_size * BYTES_PER_PIXEL;
+        let mut pixel_data = vec![0; pitch * H * pixel_size];
+        let mut row = vec![0; W * BYTES_PER_PIXEL];
+
+        for screen_y in 0..H {
+            self.fill_screen_row(screen_y, &mut row);
+
+            let scaled_row = &mut pixel_data[screen_y * pixel_size * pitch..(screen_y * pixel_size + 1) * pitch];
+            for (x, colour) in row.chunks_exact(BYTES_PER_PIXEL).enumerate() {
+                for i in 0..pixel_size {
+                    let start = (x * pixel_size + i) * BYTES_PER_PIXEL;
+                    scaled_row[start..start + BYTES_PER_PIXEL].copy_from_slice(colour);
+                }
+            }
+
+            for i in 1..pixel_size {
+                pixel_data.copy_within(
+                    screen_y * pixel_size * pitch..(screen_y * pixel_size + 1) * pitch,
+                    (screen_y * pixel_size + i) * pitch
+                );
+            }
+        }
+
+        let board = self.draw_rect();
+        let surface = Surface::from_data(&mut pixel_data, board.width(), board.height(), pitch as u32, PixelFormatEnum::RGB24).unwrap();
+        let texture = surface.as_texture(&canvas.texture_creator()).unwrap();
+        canvas.copy(&texture, None, board).unwrap();
+        // Textures aren't freed on drop with `unsafe_textures`
+        unsafe { texture.destroy(); }
+    }
+
+    pub(super) fn draw_border(&self, canvas: &mut Canvas<Window>) {
+        let board = self.draw_rect();
+        let pixel_size = self.pixel_size;
+
+        canvas.set_draw_color(Color::WHITE);
+        canvas.fill_rects(&[
+            Rect::new(board.left() - pixel_size as i32, board.top(), pixel_size, board.height() + pixel_size),
+            Rect::new(board.right(), board.top(), pixel_size, board.height() + pixel_size),
+            Rect::new(board.left(), board.bottom(), board.width(), pixel_size),
+        ]).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+use rand::Rng;
+use crate::game::{ColourCode, ColourType, Game};
+use crate::game::tetromino::{Tetromino, TetrominoShape};
+
+impl<const W: usize, const H: usize> Game<W, H> {
+    /// Board cell under a position in logical screen coordinates, if there is one
+    pub fn cell_at(&self, position: (i32, i32)) -> Option<(usize, usize)> {
+        let board = self.board_rect();
+        if !board.contains_point(position) { return None; }
+
+        let x = (position.0 - board.left()) as usize / self.pixel_size as usize;
+        let screen_y = (position.1 - board.top()) as usize / self.pixel_size as usize;
+        Some((x.min(W - 1), H - screen_y.min(H - 1) - 1))
+    }
+
+    pub fn cell(&self, cell: (usize, usize)) -> ColourType {
+        self.board[cell.0][cell.1]
+    }
+
+    /// Sets a settled cell, leaving the falling piece alone. `None` erases
+    fn set_cell(&mut self, cell: (usize, usize), code: Option<ColourCode>) {
+        if let ColourType::NoPhysicsColour(_) = self.board[cell.0][cell.1] { return; }
+
+        self.board[cell.0][cell.1] = match code {
+            Some(code) => ColourType::Colour(code),
+            None => ColourType::Empty
+        };
+        self.shades[cell.0][cell.1] = self.rng.gen();
+        self.clear_detector.mark(cell);
+    }
+
+    /// Paints sand in a disc of `radius` cells around `centre`, or erases it if `code` is `None`
+    pub fn paint(&mut self, centre: (usize, usize), radius: usize, code: Option<ColourCode>) {
+        let radius = radius as i64;
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                if dx * dx + dy * dy > radius * radius { continue; }
+
+                let (x, y) = (centre.0 as i64 + dx, centre.1 as i64 + dy);
+                if x < 0 || y < 0 || x >= W as i64 || y >= H as i64 { continue; }
+                self.set_cell((x as usize, y as usize), code);
+            }
+        }
+    }
+
+    /// Paints along the line from `from` to `to`, so quick strokes don't leave gaps
+    pub fn paint_line(&mut self, from: (usize, usize), to: (usize, usize), radius: usize, code: Option<ColourCode>) {
+        let (dx, dy) = (to.0 as i64 - from.0 as i64, to.1 as i64 - from.1 as i64);
+        let steps = dx.abs().max(dy.abs()).max(1);
+        for step in 0..=steps {
+            let x = from.0 as i64 + dx * step / steps;
+            let y = from.1 as i64 + dy * step / steps;
+            self.paint((x as usize, y as usize), radius, code);
+        }
+    }
+
+    /// Replaces the connected area of settled cells matching the one at `start` with `code`, or erases it
+    pub fn fill(&mut self, start: (usize, usize), code: Option<ColourCode>) {
+        let target = match self.board[start.0][start.1] {
+            ColourType::Empty => None,
+            ColourType::Colour(code) => Some(code),
+            ColourType::NoPhysicsColour(_) | ColourType::Deleting(_, _) => return
+        };
+        if target == code { return; }
+
+        let matches = |cell: ColourType| match target {
+            Some(target) => cell == ColourType::Colour(target),
+            None => cell.is_empty()
+        };
+
+        let mut stack = vec![start];
+        while let Some((x, y)) = stack.pop() {
+            if !matches(self.board[x][y]) { continue; }
+            self.set_cell((x, y), code);
+
+            if x > 0 { stack.push((x - 1, y)); }
+            if x < W - 1 { stack.push((x + 1, y)); }
+            if y > 0 { stack.push((x, y - 1)); }
+            if y < H - 1 { stack.push((x, y + 1)); }
+        }
+    }
+
+    /// Drops a piece centred on `cell` that falls like a spawned one.
+    /// Returns false if a piece is already falling or it doesn't fit there
+    pub fn drop_piece(&mut self, shape: TetrominoShape, code: ColourCode, cell: (usize, usize), frame_count: u64) -> bool {
+        if self.tetromino.is_some() { return false; }
+
+        let half = 2 * self.square_pixel_width as i64;
+        let tetromino = Tetromino::new(shape, (cell.0 as i64 - half, cell.1 as i64 - half), 0);
+        let fits = tetromino.get_all_pixels(self.square_pixel_width).all(|(x, y)| {
+            x >= 0 && y >= 0 && x < W as i64 && y < H as i64 && self.board[x as usize][y as usize].is_empty()
+        });
+        if !fits { return false; }
+
+        for (x, y) in tetromino.get_all_pixels(self.square_pixel_width) {
+            self.board[x as usize][y as usize] = ColourType::NoPhysicsColour(code);
+            self.shades[x as usize][y as usize] = self.rng.gen();
+        }
+        self.tetromino = Some(tetromino);
+        self.time_since_last = frame_count;
+        true
+    }
+}
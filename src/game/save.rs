@@ -0,0 +1,450 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::data_dir;
+use crate::util::write_atomically;
+use crate::game::{ColourCode, ColourType, Game, MIN_COLOURS};
+use crate::game::clear::{ClearCondition, ClearRule, Connectivity};
+use crate::game::mode::GameMode;
+use crate::game::puzzle::{Puzzle, PuzzleState, PUZZLE_DIR};
+use crate::game::palette::MAX_COLOURS;
+use crate::game::random::GameRng;
+use crate::game::tetromino::{Tetromino, TetrominoShape};
+
+const MAGIC: &[u8; 4] = b"PTSV";
+const VERSION: u8 = 1;
+
+const EMPTY: u8 = 0;
+const COLOUR: u8 = 1;
+const NO_PHYSICS_COLOUR: u8 = 2;
+const DELETING: u8 = 3;
+
+/// Where a suspended game is kept between runs
+pub fn path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("save.bin"))
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// LEB128, so the short runs that make up most of a board take a single byte
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.u8(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.u8(value as u8);
+    }
+
+    fn cell(&mut self, cell: ColourType) {
+        match cell {
+            ColourType::Empty => self.u8(EMPTY),
+            ColourType::Colour(code) => {
+                self.u8(COLOUR);
+                self.u8(code.0);
+            }
+            ColourType::NoPhysicsColour(code) => {
+                self.u8(NO_PHYSICS_COLOUR);
+                self.u8(code.0);
+            }
+            ColourType::Deleting(code, time_left) => {
+                self.u8(DELETING);
+                self.u8(code.0);
+                self.varint(time_left as u64);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(count).ok_or("Save ended early")?;
+        let bytes = self.bytes.get(self.position..end).ok_or("Save ended early")?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Invalid number in save".to_string())
+    }
+
+    fn colour_code(&mut self) -> Result<ColourCode, String> {
+        let code = self.u8()?;
+        if code as usize >= MAX_COLOURS {
+            return Err(format!("Invalid colour {} in save", code));
+        }
+        Ok(ColourCode(code))
+    }
+
+    fn cell(&mut self) -> Result<ColourType, String> {
+        Ok(match self.u8()? {
+            EMPTY => ColourType::Empty,
+            COLOUR => ColourType::Colour(self.colour_code()?),
+            NO_PHYSICS_COLOUR => ColourType::NoPhysicsColour(self.colour_code()?),
+            DELETING => ColourType::Deleting(self.colour_code()?, self.varint()? as u32),
+            tag => return Err(format!("Invalid cell type {} in save", tag))
+        })
+    }
+}
+
+impl<const W: usize, const H: usize> Game<W, H> {
+    /// Writes everything needed to carry on from this exact point. `frame_count` is the frame last passed to `game_update`
+    /// and is stored for the caller to restore
+    pub fn save(&self, path: &Path, frame_count: u64) -> Result<(), String> {
+        let mut writer = Writer { bytes: Vec::new() };
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u8(VERSION);
+        writer.u32(W as u32);
+        writer.u32(H as u32);
+        writer.u32(self.square_pixel_width);
+
+        writer.u8(GameMode::ALL.iter().position(|mode| *mode == self.mode).unwrap() as u8);
+        writer.u64(self.seed);
+        writer.u64(self.rng.state());
+        writer.u8(self.colour_count as u8);
+        match self.clear_rule.condition {
+            ClearCondition::WallToWall => writer.u8(0),
+            ClearCondition::MinimumSize(size) => {
+                writer.u8(1);
+                writer.varint(size as u64);
+            }
+            ClearCondition::FullRows => writer.u8(2),
+        }
+        writer.u8(match self.clear_rule.connectivity { Connectivity::Four => 4, Connectivity::Eight => 8 });
+
+        writer.u32(self.level);
+        writer.u32(self.clears);
+        writer.u64(self.pixels_cleared);
+        writer.u64(self.score);
+        writer.u32(self.pieces_placed);
+        writer.u32(self.chain);
+        writer.u64(frame_count);
+        writer.u64(self.time_since_last);
+        writer.u64(self.ticks);
+
+        match &self.tetromino {
+            Some(tetromino) => {
+                writer.u8(1);
+                writer.u8(TetrominoShape::ALL.iter().position(|shape| *shape == tetromino.shape()).unwrap() as u8);
+                writer.u64(tetromino.position.0 as u64);
+                writer.u64(tetromino.position.1 as u64);
+                writer.u8(tetromino.rotation);
+            }
+            None => writer.u8(0)
+        }
+
+        // Runs of identical cells, column by column
+        let mut cells = self.board.iter().flat_map(|column| column.iter().copied()).peekable();
+        while let Some(cell) = cells.next() {
+            let mut run = 1;
+            while cells.next_if_eq(&cell).is_some() {
+                run += 1;
+            }
+            writer.varint(run);
+            writer.cell(cell);
+        }
+
+        // Shades only matter where there is a pixel
+        for x in 0..W {
+            for y in 0..H {
+                if !self.board[x][y].is_empty() {
+                    writer.u8(self.shades[x][y] as u8);
+                }
+            }
+        }
+
+        // Puzzles are stored by file name and reloaded from `PUZZLE_DIR`, so only progress through the piece sequence is saved
+        match &self.puzzle {
+            Some(state) => {
+                writer.u8(1);
+                let file_name = state.puzzle.path.file_name().map_or(Default::default(), |name| name.to_string_lossy());
+                writer.varint(file_name.len() as u64);
+                writer.bytes.extend_from_slice(file_name.as_bytes());
+                writer.varint(state.next_piece as u64);
+            }
+            None => writer.u8(0)
+        }
+
+        write_atomically(path, &writer.bytes)
+    }
+
+    /// Restores a game written by `save`, returning the frame count saved with it.
+    /// Nothing is changed unless the whole file is valid
+    pub fn load(&mut self, path: &Path) -> Result<u64, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let mut reader = Reader { bytes: &bytes, position: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err("Not a save file".to_string());
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(format!("Unsupported save version {}", version));
+        }
+        if (reader.u32()?, reader.u32()?, reader.u32()?) != (W as u32, H as u32, self.square_pixel_width) {
+            return Err("Save is for a different board size".to_string());
+        }
+
+        let mode = *GameMode::ALL.get(reader.u8()? as usize).ok_or("Invalid mode in save")?;
+        let seed = reader.u64()?;
+        let rng = GameRng::from_state(reader.u64()?);
+        let colour_count = reader.u8()? as usize;
+        if !(MIN_COLOURS..=MAX_COLOURS).contains(&colour_count) {
+            return Err(format!("Invalid colour count {} in save", colour_count));
+        }
+        let condition = match reader.u8()? {
+            0 => ClearCondition::WallToWall,
+            1 => ClearCondition::MinimumSize(reader.varint()? as usize),
+            2 => ClearCondition::FullRows,
+            _ => return Err("Invalid clear rule in save".to_string())
+        };
+        let connectivity = match reader.u8()? {
+            4 => Connectivity::Four,
+            8 => Connectivity::Eight,
+            _ => return Err("Invalid connectivity in save".to_string())
+        };
+
+        let level = reader.u32()?;
+        let clears = reader.u32()?;
+        let pixels_cleared = reader.u64()?;
+        let score = reader.u64()?;
+        let pieces_placed = reader.u32()?;
+        let chain = reader.u32()?;
+        let frame_count = reader.u64()?;
+        let time_since_last = reader.u64()?;
+        if time_since_last > frame_count {
+            return Err("Invalid spawn time in save".to_string());
+        }
+        let ticks = reader.u64()?;
+
+        let tetromino = match reader.u8()? {
+            0 => None,
+            _ => {
+                let shape = *TetrominoShape::ALL.get(reader.u8()? as usize).ok_or("Invalid tetromino in save")?;
+                let position = (reader.u64()? as i64, reader.u64()? as i64);
+                let rotation = reader.u8()?;
+                if rotation > 3 {
+                    return Err("Invalid rotation in save".to_string());
+                }
+                // Checked before working out its pixels so a wild position can't overflow them
+                let reach = 4 * self.square_pixel_width as i64;
+                if !(-reach..W as i64).contains(&position.0) || !(-reach..H as i64).contains(&position.1) {
+                    return Err("Invalid tetromino position in save".to_string());
+                }
+                let tetromino = Tetromino::new(shape, position, rotation);
+                let on_board = tetromino.get_all_pixels(self.square_pixel_width)
+                    .all(|(x, y)| (0..W as i64).contains(&x) && (0..H as i64).contains(&y));
+                if !on_board {
+                    return Err("Invalid tetromino position in save".to_string());
+                }
+                Some(tetromino)
+            }
+        };
+
+        let mut board = Box::new([[ColourType::Empty; H]; W]);
+        let mut index = 0;
+        while index < W * H {
+            let run = reader.varint()? as usize;
+            let cell = reader.cell()?;
+            let end = match index.checked_add(run) {
+                Some(end) if run > 0 && end <= W * H => end,
+                _ => return Err("Invalid board in save".to_string())
+            };
+            for i in index..end {
+                board[i / H][i % H] = cell;
+            }
+            index = end;
+        }
+
+        // The falling piece is kept on the board as pixels without physics, which locking it relies on
+        let piece_pixels: Vec<(i64, i64)> = tetromino.iter().flat_map(|tetromino| tetromino.get_all_pixels(self.square_pixel_width)).collect();
+        let piece_on_board = piece_pixels.iter().all(|&(x, y)| matches!(board[x as usize][y as usize], ColourType::NoPhysicsColour(_)));
+        let loose_pixels = board.iter().flatten().filter(|cell| matches!(cell, ColourType::NoPhysicsColour(_))).count();
+        if !piece_on_board || loose_pixels != piece_pixels.len() {
+            return Err("Falling piece doesn't match the board in save".to_string());
+        }
+
+        let mut shades = Box::new([[0; H]; W]);
+        for x in 0..W {
+            for y in 0..H {
+                if !board[x][y].is_empty() {
+                    shades[x][y] = reader.u8()? as i8;
+                }
+            }
+        }
+
+        let puzzle = match reader.u8()? {
+            0 => None,
+            _ => {
+                let length = reader.varint()? as usize;
+                let file_name = String::from_utf8(reader.take(length)?.to_vec()).map_err(|_| "Invalid puzzle name in save")?;
+                // Only a bare file name, so a save can't point outside the puzzle directory
+                if Path::new(&file_name).file_name() != Some(file_name.as_ref()) {
+                    return Err("Invalid puzzle name in save".to_string());
+                }
+                let puzzle = Puzzle::load(&Path::new(PUZZLE_DIR).join(&file_name))?;
+                let next_piece = reader.varint()? as usize;
+                if next_piece > puzzle.piece_count() {
+                    return Err("Invalid puzzle progress in save".to_string());
+                }
+                Some(PuzzleState { puzzle, next_piece })
+            }
+        };
+
+        self.reset(time_since_last);
+        self.board = *board;
+        self.shades = *shades;
+        self.mode = mode;
+        self.seed = seed;
+        self.rng = rng;
+        self.colour_count = colour_count;
+        self.clear_rule = ClearRule { condition, connectivity };
+        self.level = level;
+        self.clears = clears;
+        self.pixels_cleared = pixels_cleared;
+        self.score = score;
+        self.pieces_placed = pieces_placed;
+        self.chain = chain;
+        self.ticks = ticks;
+        self.tetromino = tetromino;
+        self.puzzle = puzzle;
+        Ok(frame_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+    use crate::util::temporary_path;
+    use super::*;
+
+    const W: usize = 40;
+    const H: usize = 230;
+
+    fn game() -> Game<W, H> {
+        Game::new(1, (W as u32, H as u32), (0, 0), 10, Color::BLACK)
+    }
+
+    #[test]
+    fn round_trip_restores_identical_state() {
+        let mut saved = game();
+        saved.start(GameMode::Endless, 1234, 0);
+        for frame in 0..60 {
+            saved.game_update(frame);
+            saved.physics_update();
+        }
+        saved.board[3][0] = ColourType::Deleting(ColourCode(2), 17);
+        saved.board[4][0] = ColourType::Colour(ColourCode(1));
+        saved.shades[4][0] = -5;
+        saved.score = 99;
+
+        let path = temporary_path("round-trip.bin");
+        saved.save(&path, 60).unwrap();
+        let mut loaded = game();
+        let restored = loaded.load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored, Ok(60));
+        assert!(loaded.board == saved.board);
+        assert!(loaded.shades == saved.shades);
+        assert_eq!(loaded.board[3][0], ColourType::Deleting(ColourCode(2), 17));
+        assert_eq!(loaded.rng.state(), saved.rng.state());
+        assert_eq!(loaded.seed, 1234);
+        assert_eq!(loaded.score, 99);
+        assert_eq!(loaded.ticks, saved.ticks);
+        let (loaded_piece, saved_piece) = (loaded.tetromino.as_ref().unwrap(), saved.tetromino.as_ref().unwrap());
+        assert!(loaded_piece.shape() == saved_piece.shape());
+        assert_eq!((loaded_piece.position, loaded_piece.rotation), (saved_piece.position, saved_piece.rotation));
+    }
+
+    #[test]
+    fn round_trip_finds_the_puzzle_by_name() {
+        let mut saved = game();
+        saved.start_puzzle(Puzzle::load(&Path::new(PUZZLE_DIR).join("03-survive.txt")).unwrap(), 0).unwrap();
+        saved.puzzle.as_mut().unwrap().next_piece = 2;
+        let path = temporary_path("puzzle.bin");
+        saved.save(&path, 0).unwrap();
+
+        // Only the file name is stored, not the directory the game was started from
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.windows(PUZZLE_DIR.len()).all(|window| window != PUZZLE_DIR.as_bytes()));
+
+        let mut loaded = game();
+        let restored = loaded.load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored, Ok(0));
+        let state = loaded.puzzle.as_ref().unwrap();
+        assert_eq!(state.puzzle.path.file_name(), Some("03-survive.txt".as_ref()));
+        assert_eq!(state.next_piece, 2);
+    }
+
+    #[test]
+    fn rejects_corrupt_saves_without_changing_the_game() {
+        let mut saved = game();
+        saved.start(GameMode::Endless, 1, 0);
+        saved.tetromino = Some(Tetromino::new(TetrominoShape::ALL[0], (i64::MAX, 0), 0));
+        let path = temporary_path("corrupt.bin");
+        saved.save(&path, 0).unwrap();
+
+        let mut loaded = game();
+        loaded.score = 7;
+        assert!(loaded.load(&path).is_err());
+        assert_eq!(loaded.score, 7);
+
+        // Truncated part way through the board
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(loaded.load(&path).is_err());
+
+        // A piece over empty cells would panic when it locks
+        saved.tetromino = Some(Tetromino::new(TetrominoShape::ALL[0], (0, 100), 0));
+        saved.save(&path, 0).unwrap();
+        assert!(loaded.load(&path).is_err());
+
+        // Pixels without physics that no piece covers would never fall
+        saved.tetromino = None;
+        saved.board[0][0] = ColourType::NoPhysicsColour(ColourCode(0));
+        saved.save(&path, 0).unwrap();
+        assert!(loaded.load(&path).is_err());
+        assert_eq!(loaded.score, 7);
+        fs::remove_file(&path).unwrap();
+    }
+}
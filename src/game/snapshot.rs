@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use sdl2::pixels::Color;
+use crate::game::{ColourCode, ColourType, Game};
+use crate::game::palette::Palette;
+
+/// Darker than this counts as empty when importing, so boards can be drawn on black as well as transparent
+const BLACK_THRESHOLD: u32 = 24;
+
+/// Writes `board` as a PNG with one pixel per cell, the top row of the board at the top of the image.
+/// Empty cells are transparent. Doesn't need a window, so works headlessly
+pub fn export_board<const W: usize, const H: usize>(board: &[[ColourType; H]; W], palette: &Palette, path: &Path) -> Result<(), String> {
+    let mut data = Vec::with_capacity(W * H * 4);
+    for y in (0..H).rev() {
+        for column in board {
+            match column[y].code() {
+                Some(code) => {
+                    let colour = palette.colour(code.index());
+                    data.extend_from_slice(&[colour.r, colour.g, colour.b, 255]);
+                }
+                None => data.extend_from_slice(&[0, 0, 0, 0])
+            }
+        }
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = Encoder::new(BufWriter::new(file), W as u32, H as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())
+}
+
+/// Reads a PNG the size of the board, mapping each pixel to the nearest of the first `colour_count` palette colours.
+/// Transparent and black pixels become empty. Every other pixel gets physics
+pub fn import_board<const W: usize, const H: usize>(path: &Path, palette: &Palette, colour_count: usize) -> Result<Box<[[ColourType; H]; W]>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = Decoder::new(file);
+    // Expand palettes and low bit depths, and strip 16 bit channels, so every format reads as 8 bit channels
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+
+    if (info.width, info.height) != (W as u32, H as u32) {
+        return Err(format!("Image is {}x{}, expected {}x{}", info.width, info.height, W, H));
+    }
+
+    let channels = match info.color_type {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        ColorType::Indexed => return Err("Indexed image wasn't expanded".to_string())
+    };
+
+    let mut board = Box::new([[ColourType::Empty; H]; W]);
+    for (row, line) in buffer[..info.buffer_size()].chunks_exact(info.line_size).enumerate() {
+        let y = H - row - 1;
+        for (x, pixel) in line.chunks_exact(channels).take(W).enumerate() {
+            let (colour, alpha) = match *pixel {
+                [v] => (Color::RGB(v, v, v), 255),
+                [v, a] => (Color::RGB(v, v, v), a),
+                [r, g, b] => (Color::RGB(r, g, b), 255),
+                [r, g, b, a] => (Color::RGB(r, g, b), a),
+                _ => unreachable!()
+            };
+
+            if alpha < 128 || colour.r as u32 + colour.g as u32 + colour.b as u32 <= BLACK_THRESHOLD { continue; }
+            board[x][y] = ColourType::Colour(nearest_colour(colour, palette, colour_count));
+        }
+    }
+
+    Ok(board)
+}
+
+fn nearest_colour(colour: Color, palette: &Palette, colour_count: usize) -> ColourCode {
+    let distance = |other: Color| {
+        let (r, g, b) = (colour.r as i32 - other.r as i32, colour.g as i32 - other.g as i32, colour.b as i32 - other.b as i32);
+        r * r + g * g + b * b
+    };
+    let index = (0..colour_count).min_by_key(|&i| distance(palette.colour(i))).unwrap();
+    ColourCode(index as u8)
+}
+
+impl<const W: usize, const H: usize> Game<W, H> {
+    pub fn export_board(&self, path: &Path) -> Result<(), String> {
+        export_board(&self.board, &self.palette, path)
+    }
+
+    /// Replaces the board with one read from a PNG, removing the falling piece
+    pub fn import_board(&mut self, path: &Path) -> Result<(), String> {
+        let board = import_board::<W, H>(path, &self.palette, self.colour_count)?;
+        self.board = *board;
+        self.shades = [[0; H]; W];
+        self.tetromino = None;
+        self.clear_detector.mark_all();
+        self.frame.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::game::palette::MAX_COLOURS;
+    use crate::util::temporary_path;
+    use super::*;
+
+    const W: usize = 12;
+    const H: usize = 9;
+
+    /// Every colour, with gaps, falling and flashing cells mixed in
+    fn board() -> [[ColourType; H]; W] {
+        let mut board = [[ColourType::Empty; H]; W];
+        for (x, column) in board.iter_mut().enumerate() {
+            for (y, cell) in column.iter_mut().enumerate() {
+                let code = ColourCode(((x + y) % MAX_COLOURS) as u8);
+                *cell = match (x * 7 + y * 3) % 5 {
+                    0 => ColourType::Empty,
+                    1 => ColourType::NoPhysicsColour(code),
+                    2 => ColourType::Deleting(code, 5),
+                    _ => ColourType::Colour(code)
+                };
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn round_trip_keeps_every_colour_in_every_palette() {
+        let board = board();
+        for name in Palette::BUILT_IN {
+            let palette = Palette::built_in(name).unwrap();
+            let path = temporary_path(&format!("round-trip-{}.png", name));
+            export_board(&board, &palette, &path).unwrap();
+            let imported = import_board::<W, H>(&path, &palette, MAX_COLOURS);
+            fs::remove_file(&path).unwrap();
+            let imported = imported.unwrap();
+
+            for (x, (column, imported_column)) in board.iter().zip(imported.iter()).enumerate() {
+                for (y, (cell, imported_cell)) in column.iter().zip(imported_column).enumerate() {
+                    // Everything imported gets physics
+                    let expected = cell.code().map_or(ColourType::Empty, ColourType::Colour);
+                    assert_eq!(*imported_cell, expected, "{} at {}, {}", name, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_images_of_the_wrong_size() {
+        let palette = Palette::built_in("classic").unwrap();
+        let path = temporary_path("wrong-size.png");
+        export_board(&[[ColourType::Empty; H]; W], &palette, &path).unwrap();
+        let imported = import_board::<H, W>(&path, &palette, MAX_COLOURS);
+        fs::remove_file(&path).unwrap();
+        assert!(imported.is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use rand::distributions::{Distribution, Standard};
 use rand::Rng;
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum TetrominoShape {
     LShape,
     ReverseLShape,
@@ -11,6 +11,29 @@ pub enum TetrominoShape {
     Square,
 }
 
+impl TetrominoShape {
+    pub const ALL: [TetrominoShape; 6] = [
+        TetrominoShape::LShape, TetrominoShape::ReverseLShape, TetrominoShape::ZShape,
+        TetrominoShape::ReverseZShape, TetrominoShape::Line, TetrominoShape::Square,
+    ];
+
+    /// The usual single letter name for the shape
+    pub fn name(&self) -> &'static str {
+        match self {
+            TetrominoShape::LShape => "L",
+            TetrominoShape::ReverseLShape => "J",
+            TetrominoShape::ZShape => "Z",
+            TetrominoShape::ReverseZShape => "S",
+            TetrominoShape::Line => "I",
+            TetrominoShape::Square => "O",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|shape| shape.name().eq_ignore_ascii_case(name))
+    }
+}
+
 impl Distribution<TetrominoShape> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> TetrominoShape {
         match rng.gen_range(0..6) {
@@ -41,6 +64,10 @@ impl Tetromino {
         }
     }
 
+    pub fn shape(&self) -> TetrominoShape {
+        self.shape
+    }
+
     pub fn get_shape(&self) -> [[bool; 4]; 4] {
         match self.shape {
             TetrominoShape::LShape =>
@@ -0,0 +1,143 @@
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::col_println;
+use crate::game::mode::{GameMode, Ranking};
+use crate::highscores::{HighScore, HighScores, MAX_NAME_LENGTH};
+use crate::hud::format_duration;
+use crate::menu::{draw_centred, draw_overlay, MenuInput, ITEM_SCALE, TITLE_SCALE};
+use crate::score_table::ScoreTable;
+use crate::text::Font;
+
+const DEFAULT_NAME: &str = "PLAYER";
+const NAME_CHARACTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const LABEL_COLOUR: Color = Color::RGB(150, 150, 150);
+
+enum Page {
+    /// Typing a name for a score that made the table
+    NameEntry(String),
+    Results,
+    Table(ScoreTable),
+}
+
+/// Shown when a game ends, asking for a name if the score made the high score table
+pub struct GameOverScreen {
+    page: Page,
+    mode: GameMode,
+    score: HighScore,
+    /// Whether the mode's end condition was met rather than the stack topping out
+    finished: bool,
+}
+
+impl GameOverScreen {
+    /// `score` is recorded under the name the player enters. Games ranked by time only count if `finished`
+    pub fn new(mode: GameMode, score: HighScore, finished: bool, high_scores: &HighScores) -> Self {
+        let ranked = finished || mode.ranking() == Ranking::Score;
+        let page = if ranked && high_scores.qualifies(mode, &score) { Page::NameEntry(String::new()) } else { Page::Results };
+        Self { page, mode, score, finished }
+    }
+
+    fn title(&self) -> &'static str {
+        match (self.mode, self.finished) {
+            (GameMode::Sprint, true) => "Finished!",
+            (GameMode::Sprint, false) => "Failed",
+            (GameMode::Ultra, true) => "Time up!",
+            _ => "Game over"
+        }
+    }
+
+    fn results(&self) -> Vec<String> {
+        let score = &self.score;
+        match self.mode {
+            GameMode::Sprint if self.finished => vec![
+                format!("Time: {}", format_duration(score.duration)),
+                format!("Score: {}", score.score),
+            ],
+            GameMode::Sprint => vec![
+                format!("Clears: {}", score.clears),
+                format!("Time: {}", format_duration(score.duration)),
+            ],
+            GameMode::Ultra => vec![
+                format!("Score: {}", score.score),
+                format!("Clears: {}", score.clears),
+                format!("Level: {}", score.level),
+            ],
+            GameMode::Endless | GameMode::Puzzle | GameMode::Zen | GameMode::Sandbox => vec![
+                format!("Score: {}", score.score),
+                format!("Level: {}", score.level),
+                format!("Clears: {}", score.clears),
+                format!("Time: {}", format_duration(score.duration)),
+            ],
+        }
+    }
+
+    /// Returns true once the player is done and should go back to the title screen
+    pub fn handle_event(&mut self, event: &Event, high_scores: &mut HighScores) -> bool {
+        let name = match &mut self.page {
+            Page::NameEntry(name) => name,
+            Page::Results => return MenuInput::from_event(event).map_or(false, |input| matches!(input, MenuInput::Select | MenuInput::Back)),
+            Page::Table(table) => return MenuInput::from_event(event).map_or(false, |input| table.handle_input(input))
+        };
+
+        match event {
+            Event::TextInput { text, .. } => {
+                for c in text.chars().filter(char::is_ascii_alphanumeric) {
+                    if name.len() < MAX_NAME_LENGTH {
+                        name.push(c.to_ascii_uppercase());
+                    }
+                }
+            }
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } | Event::ControllerButtonDown { button: Button::DPadLeft, .. } => {
+                name.pop();
+            }
+            Event::ControllerButtonDown { button: Button::DPadRight, .. } if name.len() < MAX_NAME_LENGTH => name.push('A'),
+            Event::ControllerButtonDown { button: button @ (Button::DPadUp | Button::DPadDown), .. } => {
+                let step = if *button == Button::DPadUp { 1 } else { NAME_CHARACTERS.len() - 1 };
+                let index = name.pop().and_then(|c| NAME_CHARACTERS.find(c)).map_or(0, |i| (i + step) % NAME_CHARACTERS.len());
+                name.push(NAME_CHARACTERS.as_bytes()[index] as char);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Return), .. } | Event::ControllerButtonDown { button: Button::A, .. } => {
+                self.score.name = if name.is_empty() { DEFAULT_NAME.to_string() } else { name.clone() };
+                let rank = high_scores.insert(self.mode, self.score.clone());
+                if let Err(e) = high_scores.save() {
+                    col_println!((red, bold), "Failed to save high scores: {}", e);
+                }
+                self.page = Page::Table(ScoreTable::new(self.mode, rank));
+            }
+            _ => {}
+        }
+        false
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &Font, area: Rect, high_scores: &HighScores) {
+        if let Page::Table(table) = &self.page {
+            table.draw(canvas, font, area, high_scores);
+            return;
+        }
+
+        draw_overlay(canvas, area);
+        let line = font.line_height(ITEM_SCALE) as i32;
+        let mut y = area.center().y() - line * 6;
+        draw_centred(canvas, font, self.title(), area, y, TITLE_SCALE, Color::WHITE);
+        y += font.line_height(TITLE_SCALE) as i32 * 2;
+
+        for result in &self.results() {
+            draw_centred(canvas, font, result, area, y, ITEM_SCALE, Color::WHITE);
+            y += line;
+        }
+        y += line;
+
+        match &self.page {
+            Page::NameEntry(name) => {
+                draw_centred(canvas, font, "New high score! Enter your name", area, y, ITEM_SCALE, LABEL_COLOUR);
+                y += line;
+                draw_centred(canvas, font, &format!("{}_", name), area, y, ITEM_SCALE, Color::WHITE);
+            }
+            _ => draw_centred(canvas, font, "Press enter", area, y, ITEM_SCALE, LABEL_COLOUR)
+        }
+    }
+}
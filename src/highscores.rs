@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::col_println;
+use crate::config::data_dir;
+use crate::util::write_atomically;
+use crate::game::mode::GameMode;
+
+const SCHEMA_VERSION: u32 = 1;
+const SCORES_PER_MODE: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u64,
+    pub level: u32,
+    pub clears: u32,
+    pub duration: Duration,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub seed: u64,
+}
+
+impl HighScore {
+    /// Date the score was set as `YYYY-MM-DD` in UTC
+    pub fn date(&self) -> String {
+        // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+        let days = (self.timestamp / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!("{}-{:02}-{:02}", year, month, day)
+    }
+
+    /// Score and time, as compared by `GameMode::compare`
+    fn result(&self) -> (u64, Duration) {
+        (self.score, self.duration)
+    }
+
+    fn parse(fields: &[&str]) -> Result<Self, String> {
+        let number = |i: usize| fields[i].parse::<u64>().map_err(|_| format!("Invalid number '{}'", fields[i]));
+        Ok(Self {
+            name: fields[1].to_string(),
+            score: number(2)?,
+            level: number(3)? as u32,
+            clears: number(4)? as u32,
+            duration: Duration::from_millis(number(5)?),
+            timestamp: number(6)?,
+            seed: number(7)?
+        })
+    }
+}
+
+pub fn now_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+fn mode_key(mode: GameMode) -> String {
+    mode.name().to_lowercase()
+}
+
+/// The best scores for each mode, saved in `highscores.txt` in the data directory.
+/// The file starts with `version <n>` followed by one tab separated score per line
+pub struct HighScores {
+    scores: Vec<(GameMode, Vec<HighScore>)>,
+}
+
+impl HighScores {
+    pub fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("highscores.txt"))
+    }
+
+    /// Loads the saved scores, starting empty if there are none or the file can't be read
+    pub fn load() -> Self {
+        let mut high_scores = Self { scores: GameMode::with_high_scores().map(|mode| (mode, Vec::new())).collect() };
+        let source = match Self::path().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(source) => source,
+            None => return high_scores
+        };
+
+        if let Err(e) = high_scores.parse(&source) {
+            col_println!((yellow, bold), "Ignoring high scores: {}", e);
+            for (_, scores) in &mut high_scores.scores {
+                scores.clear();
+            }
+        }
+        high_scores
+    }
+
+    fn parse(&mut self, source: &str) -> Result<(), String> {
+        let mut lines = source.lines();
+        match lines.next().and_then(|line| line.strip_prefix("version ")).map(|version| version.trim().parse::<u32>()) {
+            Some(Ok(SCHEMA_VERSION)) => {}
+            Some(Ok(version)) => return Err(format!("Unsupported version {}", version)),
+            _ => return Err("Missing version".to_string())
+        }
+
+        for line in lines {
+            if line.trim().is_empty() { continue; }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 8 {
+                return Err(format!("Invalid line '{}'", line));
+            }
+            // Scores for modes this version doesn't have are dropped
+            if let Some((_, scores)) = self.scores.iter_mut().find(|(mode, _)| mode_key(*mode) == fields[0]) {
+                scores.push(HighScore::parse(&fields)?);
+            }
+        }
+
+        for (mode, scores) in &mut self.scores {
+            scores.sort_by(|a, b| mode.compare(a.result(), b.result()));
+            scores.truncate(SCORES_PER_MODE);
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("No data directory")?;
+        let mut source = format!("version {}\n", SCHEMA_VERSION);
+        for (mode, scores) in &self.scores {
+            for score in scores {
+                source += &format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    mode_key(*mode), score.name, score.score, score.level, score.clears,
+                    score.duration.as_millis(), score.timestamp, score.seed
+                );
+            }
+        }
+
+        write_atomically(&path, source.as_bytes())
+    }
+
+    pub fn scores(&self, mode: GameMode) -> &[HighScore] {
+        self.scores.iter().find(|(m, _)| *m == mode).map_or(&[], |(_, scores)| scores)
+    }
+
+    /// Whether `high_score` would make it onto the table for `mode`
+    pub fn qualifies(&self, mode: GameMode, high_score: &HighScore) -> bool {
+        match self.scores.iter().find(|(m, _)| *m == mode) {
+            Some((_, scores)) => scores.len() < SCORES_PER_MODE
+                || scores.iter().any(|s| mode.compare(high_score.result(), s.result()) == Ordering::Less),
+            None => false
+        }
+    }
+
+    /// Adds a score, returning its position in the table if it made it on
+    pub fn insert(&mut self, mode: GameMode, high_score: HighScore) -> Option<usize> {
+        let scores = match self.scores.iter_mut().find(|(m, _)| *m == mode) {
+            Some((_, scores)) => scores,
+            None => return None
+        };
+
+        let rank = scores.iter().position(|s| mode.compare(high_score.result(), s.result()) == Ordering::Less).unwrap_or(scores.len());
+        if rank >= SCORES_PER_MODE { return None; }
+
+        scores.insert(rank, high_score);
+        scores.truncate(SCORES_PER_MODE);
+        Some(rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty() -> HighScores {
+        HighScores { scores: GameMode::with_high_scores().map(|mode| (mode, Vec::new())).collect() }
+    }
+
+    fn high_score(score: u64, seconds: u64) -> HighScore {
+        HighScore { name: "AAA".to_string(), score, level: 0, clears: 0, duration: Duration::from_secs(seconds), timestamp: 0, seed: 0 }
+    }
+
+    fn fill(high_scores: &mut HighScores, mode: GameMode) {
+        for i in 1..=SCORES_PER_MODE as u64 {
+            high_scores.insert(mode, high_score(i * 100, i * 10));
+        }
+    }
+
+    #[test]
+    fn anything_qualifies_until_the_table_is_full() {
+        let mut high_scores = empty();
+        assert!(high_scores.qualifies(GameMode::Endless, &high_score(0, 0)));
+
+        fill(&mut high_scores, GameMode::Endless);
+        assert!(!high_scores.qualifies(GameMode::Endless, &high_score(100, 0)));
+        assert!(high_scores.qualifies(GameMode::Endless, &high_score(101, 0)));
+    }
+
+    #[test]
+    fn sprint_qualifies_and_inserts_by_time() {
+        let mut high_scores = empty();
+        fill(&mut high_scores, GameMode::Sprint);
+        assert!(!high_scores.qualifies(GameMode::Sprint, &high_score(10_000, SCORES_PER_MODE as u64 * 10)));
+        assert!(high_scores.qualifies(GameMode::Sprint, &high_score(0, 15)));
+
+        assert_eq!(high_scores.insert(GameMode::Sprint, high_score(0, 15)), Some(1));
+        let times: Vec<u64> = high_scores.scores(GameMode::Sprint).iter().map(|s| s.duration.as_secs()).collect();
+        assert_eq!(times.len(), SCORES_PER_MODE);
+        assert_eq!(&times[..3], &[10, 15, 20]);
+    }
+
+    #[test]
+    fn unranked_modes_never_qualify() {
+        let mut high_scores = empty();
+        assert!(!high_scores.qualifies(GameMode::Puzzle, &high_score(1_000, 0)));
+        assert_eq!(high_scores.insert(GameMode::Puzzle, high_score(1_000, 0)), None);
+    }
+
+    #[test]
+    fn parse_sorts_and_drops_unknown_modes() {
+        let mut high_scores = empty();
+        high_scores.parse("version 1\nendless\tA\t100\t1\t2\t3000\t0\t0\nendless\tB\t300\t1\t2\t3000\t0\t0\nmarathon\tC\t900\t1\t2\t3000\t0\t0\n").unwrap();
+
+        let names: Vec<&str> = high_scores.scores(GameMode::Endless).iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["B", "A"]);
+        assert!(empty().parse("version 2\n").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::game::Game;
+use crate::game::mode::EndCondition;
+use crate::text::Font;
+
+const HUD_MARGIN: i32 = 20;
+const HUD_SCALE: u32 = 3;
+const LABEL_COLOUR: Color = Color::RGB(150, 150, 150);
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}.{:02}", seconds / 60, seconds % 60, duration.subsec_millis() / 10)
+}
+
+/// Draws the score, level, clears, time and pieces placed to the right of the board, and the goal when playing a puzzle
+pub fn draw<const W: usize, const H: usize>(canvas: &mut Canvas<Window>, font: &Font, game: &Game<W, H>) {
+    let board = game.board_rect();
+    let x = board.right() + HUD_MARGIN;
+    let mut y = board.top();
+
+    // Timed modes count down, and sprint counts towards its target
+    let clears = match game.mode().end_condition() {
+        EndCondition::Clears(target) => format!("{}/{}", game.clears(), target),
+        _ => game.clears().to_string()
+    };
+    let mut entries = vec![
+        ("Score", game.score().to_string()),
+        ("Level", game.level().to_string()),
+        ("Clears", clears),
+        ("Time", format_duration(game.time_left().unwrap_or(game.elapsed()))),
+        ("Pieces", game.pieces_placed().to_string()),
+    ];
+    if let (Some(puzzle), Some(pieces_left)) = (game.puzzle(), game.pieces_left()) {
+        entries.push(("Goal", puzzle.goal.summary()));
+        entries.push(("Left", pieces_left.to_string()));
+    }
+
+    for (label, value) in entries {
+        font.draw(canvas, label, (x, y), HUD_SCALE, LABEL_COLOUR);
+        y += font.line_height(HUD_SCALE) as i32;
+        font.draw(canvas, &value, (x, y), HUD_SCALE, Color::WHITE);
+        y += font.line_height(HUD_SCALE) as i32 * 2;
+    }
+
+    if !game.assist_enabled() { return; }
+
+    // How close the largest region of each colour is to spanning the board
+    font.draw(canvas, "Assist", (x, y), HUD_SCALE, LABEL_COLOUR);
+    y += font.line_height(HUD_SCALE) as i32;
+    let swatch_size = font.line_height(HUD_SCALE) - HUD_SCALE * 2;
+    for preview in game.assist_previews() {
+        canvas.set_draw_color(game.palette().colour(preview.code.index()));
+        canvas.fill_rect(Rect::new(x, y, swatch_size, swatch_size)).unwrap();
+
+        let span = format!("{}%", (preview.progress * 100.0) as u32);
+        font.draw(canvas, &span, (x + swatch_size as i32 + HUD_MARGIN / 2, y), HUD_SCALE, Color::WHITE);
+        y += font.line_height(HUD_SCALE) as i32;
+    }
+}
+
+/// Marks the top left corner of the screen while frames are being recorded
+pub fn draw_recording(canvas: &mut Canvas<Window>, font: &Font) {
+    font.draw(canvas, "Rec", (HUD_MARGIN, HUD_MARGIN), HUD_SCALE, Color::RED);
+}
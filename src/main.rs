@@ -1,34 +1,72 @@
 mod game;
 mod util;
-
+mod text;
+mod hud;
+mod display;
+mod audio;
+mod config;
+mod menu;
+mod pause;
+mod settings;
+mod title;
+mod highscores;
+mod score_table;
+mod game_over;
+mod capture;
+mod puzzles;
+mod sandbox;
+mod debug;
+
+use std::fs;
 use std::io;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 use rand::prelude::ThreadRng;
 use rand::{Rng, RngCore};
-use sdl2::event::Event;
-use sdl2::{EventPump};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::{EventPump, Sdl};
 use sdl2::controller::Button;
 use sdl2::gfx::framerate::FPSManager;
 use sdl2::keyboard::Keycode;
 use sdl2::mixer::{AUDIO_S16LSB, DEFAULT_CHANNELS, InitFlag, Music};
 use sdl2::mouse::SystemCursor::No;
 use sdl2::pixels::Color;
+use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
-use crate::game::Game;
-
-
-fn initialise_display(resolution: (u32, u32)) -> (Canvas<Window>, EventPump) {
+use crate::audio::Audio;
+use crate::capture::Recorder;
+use crate::config::{Config, data_dir};
+use crate::display::Display;
+use crate::game::{Game, GameEvent};
+use crate::game::mode::GameMode;
+use crate::game::puzzle::Puzzle;
+use crate::game::save;
+use crate::game::render::RenderBackend;
+use crate::menu::MenuInput;
+use crate::pause::{PauseMenu, PauseOutcome};
+use crate::title::{TitleOutcome, TitleScreen};
+use crate::highscores::{HighScore, HighScores, now_timestamp};
+use crate::game_over::GameOverScreen;
+use crate::sandbox::Sandbox;
+use crate::debug::{DebugControls, PHYSICS_TICKS_PER_FRAME};
+use crate::puzzles::{next_puzzle, PuzzleEnd, PuzzleEndOutcome, PuzzleSelect, PuzzleSelectOutcome};
+use crate::text::Font;
+
+
+fn initialise_display(resolution: (u32, u32)) -> (Sdl, Canvas<Window>, EventPump) {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
+    // Keep the board crisp when the renderer scales it up
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
     // sdl_context.mouse().show_cursor(false);
 
     let window = video_subsystem
         .window("Tetris", resolution.0, resolution.1)
         .position_centered()
-        // .fullscreen()
+        .resizable()
         .build()
         .unwrap();
 
@@ -38,7 +76,45 @@ fn initialise_display(resolution: (u32, u32)) -> (Canvas<Window>, EventPump) {
 
     let event_pump = sdl_context.event_pump().unwrap();
 
-    (canvas, event_pump)
+    (sdl_context, canvas, event_pump)
+}
+
+/// What the main loop is currently showing
+enum Screen {
+    Title(TitleScreen),
+    Playing,
+    Paused(PauseMenu),
+    GameOver(GameOverScreen),
+    PuzzleSelect(PuzzleSelect),
+    PuzzleEnd(PuzzleEnd),
+    Sandbox(Sandbox),
+}
+
+/// Title screen offering to continue if there is a suspended game
+fn title_screen() -> TitleScreen {
+    TitleScreen::new(save::path().map_or(false, |path| path.exists()))
+}
+
+/// Starts a new game, on the board from `start_board` if one was given
+fn start_game<const W: usize, const H: usize>(game: &mut Game<W, H>, mode: GameMode, seed: u64, frame_count: u64, start_board: Option<&Path>) {
+    game.start(mode, seed, frame_count);
+    if let Some(path) = start_board {
+        if let Err(e) = game.import_board(path) {
+            col_println!((red, bold), "Failed to load board '{}': {}", path.display(), e);
+        }
+    }
+}
+
+/// Starts `puzzle`, returning false if its board couldn't be loaded
+fn start_puzzle<const W: usize, const H: usize>(game: &mut Game<W, H>, puzzle: Puzzle, frame_count: u64) -> bool {
+    let name = puzzle.name.clone();
+    match game.start_puzzle(puzzle, frame_count) {
+        Ok(()) => true,
+        Err(e) => {
+            col_println!((red, bold), "Failed to start puzzle '{}': {}", name, e);
+            false
+        }
+    }
 }
 
 fn reset_timer(frame_count: u64, log_rate: u64, instant: &mut Instant) {
@@ -57,7 +133,21 @@ pub fn main() -> Result<(), String> {
     const RESOLUTION: (u32, u32) = (1080, 1080);
     const FRAMERATE: u32 = 60;
 
-    let (mut canvas, mut event_pump) = initialise_display(RESOLUTION);
+    // Exports the suspended game's board without opening a window
+    if let Some(output) = std::env::args().find_map(|arg| arg.strip_prefix("--export-save=").map(PathBuf::from)) {
+        let mut game = Game::<WIDTH, HEIGHT>::new(PIXEL_SIZE as u32, RESOLUTION, (0, 0), SQUARE_PIXEL_WIDTH as u32, Color::BLACK);
+        game.set_palette(Config::load().palette());
+        game.load(&save::path().ok_or("No data directory")?)?;
+        game.export_board(&output)?;
+        col_println!((green, bold), "Exported board to {}", output.display());
+        return Ok(());
+    }
+
+    // Board every game starts from, for reproducing bug reports
+    let start_board = std::env::args().find_map(|arg| arg.strip_prefix("--board=").map(PathBuf::from));
+
+    let (sdl_context, mut canvas, mut event_pump) = initialise_display(RESOLUTION);
+    let mut display = Display::new(RESOLUTION);
     let mut rng = rand::thread_rng();
     let mut fps = FPSManager::new();
     fps.set_framerate(FRAMERATE)?;
@@ -83,9 +173,31 @@ pub fn main() -> Result<(), String> {
             Color::BLACK
         );
 
-    // sdl2::mixer::init(InitFlag::MP3 | InitFlag::FLAC | InitFlag::MOD | InitFlag::OGG)?;
-    // let music = Music::from_file("static/Tetris.mp3")?;
-    // music.play(-1)?;
+    game.set_layout(display.update(&mut canvas)?);
+
+    let config = Config::load();
+    game.set_palette(config.palette());
+    game.set_colour_count(config.colours);
+    game.set_screen_shake(config.screen_shake);
+    game.set_assist(config.assist);
+    for mode in GameMode::ALL {
+        game.set_clear_rule(mode, config.clear_rule(mode));
+    }
+
+    if let Some(backend) = std::env::args().find_map(|arg| arg.strip_prefix("--renderer=").and_then(RenderBackend::from_name)) {
+        game.set_render_backend(backend);
+    }
+
+    let font = Font::load("static/font.txt");
+
+    let controller_subsystem = sdl_context.game_controller()?;
+    let mut controllers = Vec::new();
+    let mut screen = Screen::Title(title_screen());
+    let mut high_scores = HighScores::load();
+    let mut recorder = Recorder::new((WIDTH as u32, HEIGHT as u32));
+    let mut debug = if std::env::args().any(|arg| arg == "--debug") { Some(DebugControls::new()) } else { None };
+
+    let mut audio = Audio::new(&sdl_context);
 
     #[cfg(log)]
     const FPS_LOG_RATE: u64 = 100;
@@ -99,6 +211,8 @@ pub fn main() -> Result<(), String> {
     const KEY_SCAN_RATE: u64 = 30;
 
     let mut frame_count: u64 = 0;
+    // Frames the game has actually run for, which stops while paused
+    let mut game_frame: u64 = 0;
     'main_loop: loop {
         #[cfg(log)]
         if frame_count % FPS_LOG_RATE == 1 {
@@ -111,16 +225,213 @@ pub fn main() -> Result<(), String> {
         }
 
 
+        let mut take_screenshot = false;
+
         #[cfg(log)]
         reset_timer(frame_count, PROFILING_LOG_RATE, &mut profile_timer);
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::P), .. } => break 'main_loop,
+                Event::Quit { .. } => break 'main_loop,
+                Event::ControllerDeviceAdded { which, .. } => match controller_subsystem.open(which) {
+                    Ok(controller) => controllers.push(controller),
+                    Err(e) => col_println!((yellow, bold), "Failed to open controller: {}", e)
+                },
+                Event::ControllerDeviceRemoved { which, .. } => controllers.retain(|controller| controller.instance_id() != which),
+                Event::Window { win_event: WindowEvent::SizeChanged(..), .. } => match display.update(&mut canvas) {
+                    Ok(size) => game.set_layout(size),
+                    Err(e) => col_println!((red, bold), "Failed to resize: {}", e)
+                },
+                _ if debug.as_mut().map_or(false, |debug| debug.handle_event(&event, &display)) => {},
+                _ if matches!(screen, Screen::GameOver(_)) => {
+                    let game_over = match &mut screen {
+                        Screen::GameOver(game_over) => game_over,
+                        _ => unreachable!()
+                    };
+                    if game_over.handle_event(&event, &mut high_scores) {
+                        game.start(GameMode::Endless, 0, game_frame);
+                        audio.restart_music();
+                        screen = Screen::Title(title_screen());
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F11), .. } => match display.toggle_fullscreen(&mut canvas) {
+                    Ok(()) => {}
+                    Err(e) => col_println!((red, bold), "Failed to toggle fullscreen: {}", e)
+                },
+                Event::KeyDown { keycode: Some(Keycode::F10), .. } => match display.toggle_integer_scale(&mut canvas) {
+                    Ok(size) => game.set_layout(size),
+                    Err(e) => col_println!((red, bold), "Failed to change scaling: {}", e)
+                },
+                Event::KeyDown { keycode: Some(Keycode::M), .. } => audio.toggle_mute(),
+                Event::KeyDown { keycode: Some(Keycode::Equals), .. } => audio.volume_up(),
+                Event::KeyDown { keycode: Some(Keycode::Minus), .. } => audio.volume_down(),
+                Event::KeyDown { keycode: Some(Keycode::F3), .. } => game.set_palette(game.palette().cycle_built_in(1)),
+                Event::KeyDown { keycode: Some(Keycode::F4), .. } => {
+                    let mut palette = game.palette().clone();
+                    palette.patterns = !palette.patterns;
+                    game.set_palette(palette);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F5), .. } => game.set_assist(!game.assist_enabled()),
+                Event::KeyDown { keycode: Some(Keycode::F2), .. } => {
+                    game.set_render_backend(game.render_backend().next());
+                    col_println!((blue, bold), "Render backend: {:?}", game.render_backend());
+                },
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => take_screenshot = true,
+                Event::KeyDown { keycode: Some(Keycode::F9), .. } => {
+                    if let Err(e) = recorder.toggle() {
+                        col_println!((red, bold), "Failed to start recording: {}", e);
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                    let directory = match data_dir() {
+                        Some(directory) => directory.join("boards"),
+                        None => {
+                            col_println!((red, bold), "Failed to save board: No data directory");
+                            continue;
+                        }
+                    };
+                    let path = directory.join(format!("board-{}.png", now_timestamp()));
+                    match fs::create_dir_all(&directory).map_err(|e| e.to_string()).and_then(|_| game.export_board(&path)) {
+                        Ok(()) => col_println!((green, bold), "Saved board to {}", path.display()),
+                        Err(e) => col_println!((red, bold), "Failed to save board: {}", e)
+                    }
+                },
+                _ if matches!(screen, Screen::Sandbox(_)) => {
+                    let sandbox = match &mut screen {
+                        Screen::Sandbox(sandbox) => sandbox,
+                        _ => unreachable!()
+                    };
+                    if sandbox.handle_event(&event, &mut game, &display, game_frame) {
+                        game.start(GameMode::Endless, 0, game_frame);
+                        audio.restart_music();
+                        screen = Screen::Title(title_screen());
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::P), .. } | Event::ControllerButtonDown { button: Button::Start, .. } if matches!(screen, Screen::Paused(_)) => {
+                    screen = Screen::Playing;
+                    audio.resume_music();
+                },
+                _ if !matches!(screen, Screen::Playing) => {
+                    let input = match MenuInput::from_event(&event) {
+                        Some(input) => input,
+                        None => continue
+                    };
+                    match &mut screen {
+                        Screen::Title(title) => match title.handle_input(input, &mut game, &mut audio) {
+                            Some(TitleOutcome::Start(GameMode::Puzzle)) => screen = Screen::PuzzleSelect(PuzzleSelect::new()),
+                            Some(TitleOutcome::Start(mode)) => {
+                                start_game(&mut game, mode, rng.gen(), game_frame, start_board.as_deref());
+                                audio.restart_music();
+                                screen = if mode == GameMode::Sandbox { Screen::Sandbox(Sandbox::new()) } else { Screen::Playing };
+                            }
+                            Some(TitleOutcome::Continue) => {
+                                let path = match save::path() {
+                                    Some(path) => path,
+                                    None => {
+                                        col_println!((red, bold), "Failed to load saved game: No data directory");
+                                        continue;
+                                    }
+                                };
+                                match game.load(&path) {
+                                    Ok(frame) => {
+                                        // A suspended game can only be continued once, so it can't be used as a checkpoint.
+                                        // It's kept if loading fails so it isn't lost to a passing error
+                                        if let Err(e) = fs::remove_file(&path) {
+                                            col_println!((yellow, bold), "Failed to remove saved game: {}", e);
+                                        }
+                                        game_frame = frame;
+                                        audio.restart_music();
+                                        screen = Screen::Playing;
+                                    }
+                                    Err(e) => {
+                                        col_println!((red, bold), "Failed to load saved game: {}", e);
+                                        screen = Screen::Title(title_screen());
+                                    }
+                                }
+                            }
+                            Some(TitleOutcome::Quit) => break 'main_loop,
+                            None => {}
+                        },
+                        Screen::Paused(pause_menu) => match pause_menu.handle_input(input, &mut game, &mut audio) {
+                            Some(PauseOutcome::Resume) => {
+                                screen = Screen::Playing;
+                                audio.resume_music();
+                            }
+                            Some(PauseOutcome::Restart) => {
+                                match game.puzzle().cloned() {
+                                    Some(puzzle) => { start_puzzle(&mut game, puzzle, game_frame); }
+                                    None => {
+                                        let mode = game.mode();
+                                        start_game(&mut game, mode, rng.gen(), game_frame, start_board.as_deref());
+                                    }
+                                }
+                                audio.restart_music();
+                                screen = Screen::Playing;
+                            }
+                            Some(PauseOutcome::SaveAndQuit) => {
+                                match save::path().ok_or("No data directory".to_string()).and_then(|path| game.save(&path, game_frame)) {
+                                    Ok(()) => {
+                                        game.start(GameMode::Endless, 0, game_frame);
+                                        audio.restart_music();
+                                        screen = Screen::Title(title_screen());
+                                    }
+                                    // Stay paused so the game isn't lost
+                                    Err(e) => col_println!((red, bold), "Failed to save game: {}", e)
+                                }
+                            }
+                            Some(PauseOutcome::QuitToTitle) => {
+                                game.start(GameMode::Endless, 0, game_frame);
+                                audio.restart_music();
+                                screen = Screen::Title(title_screen());
+                            }
+                            None => {}
+                        },
+                        Screen::PuzzleSelect(puzzle_select) => match puzzle_select.handle_input(input) {
+                            Some(PuzzleSelectOutcome::Start(index)) if start_puzzle(&mut game, puzzle_select.puzzles()[index].clone(), game_frame) => {
+                                audio.restart_music();
+                                screen = Screen::Playing;
+                            }
+                            Some(PuzzleSelectOutcome::Back) => screen = Screen::Title(title_screen()),
+                            Some(PuzzleSelectOutcome::Start(_)) | None => {}
+                        },
+                        Screen::PuzzleEnd(puzzle_end) => match puzzle_end.handle_input(input) {
+                            Some(outcome @ (PuzzleEndOutcome::Next | PuzzleEndOutcome::Retry)) => {
+                                let puzzle = match outcome {
+                                    PuzzleEndOutcome::Next => game.puzzle().and_then(next_puzzle),
+                                    _ => game.puzzle().cloned()
+                                };
+                                if let Some(puzzle) = puzzle {
+                                    if start_puzzle(&mut game, puzzle, game_frame) {
+                                        audio.restart_music();
+                                        screen = Screen::Playing;
+                                    }
+                                }
+                            }
+                            Some(PuzzleEndOutcome::LevelSelect) => {
+                                game.start(GameMode::Endless, 0, game_frame);
+                                audio.restart_music();
+                                screen = Screen::PuzzleSelect(PuzzleSelect::new());
+                            }
+                            Some(PuzzleEndOutcome::QuitToTitle) => {
+                                game.start(GameMode::Endless, 0, game_frame);
+                                audio.restart_music();
+                                screen = Screen::Title(title_screen());
+                            }
+                            None => {}
+                        },
+                        Screen::Playing | Screen::GameOver(_) | Screen::Sandbox(_) => {}
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::P | Keycode::Escape), .. }
+                | Event::ControllerButtonDown { button: Button::Start, .. }
+                | Event::Window { win_event: WindowEvent::FocusLost, .. } => {
+                    screen = Screen::Paused(PauseMenu::new());
+                    audio.pause_music();
+                },
                 Event::KeyDown { keycode: Some(Keycode::Left), .. } | Event::ControllerButtonDown { button: Button::DPadLeft, .. } => game.move_left(),
-                Event::KeyDown { keycode: Some(Keycode::Right), .. } => game.move_right(),
-                Event::KeyDown { keycode: Some(Keycode::Down), .. } => game.move_down(),
-                Event::KeyDown { keycode: Some(Keycode::Up), .. } => game.move_down_amount(10_000),
-                Event::KeyDown { keycode: Some(Keycode::R), .. } => game.rotate(),
+                Event::KeyDown { keycode: Some(Keycode::Right), .. } | Event::ControllerButtonDown { button: Button::DPadRight, .. } => game.move_right(),
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } | Event::ControllerButtonDown { button: Button::DPadDown, .. } => game.move_down(),
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } | Event::ControllerButtonDown { button: Button::DPadUp, .. } => game.move_down_amount(10_000),
+                Event::KeyDown { keycode: Some(Keycode::R), .. } | Event::ControllerButtonDown { button: Button::A, .. } => game.rotate(),
                 _ => {}
             }
         }
@@ -155,20 +466,100 @@ pub fn main() -> Result<(), String> {
 
         #[cfg(log)]
         reset_timer(frame_count, PROFILING_LOG_RATE, &mut profile_timer);
-        game.game_update(&mut rng, frame_count);
+        // The sandbox can pause the simulation or step it a frame at a time
+        let stepping = match &mut screen {
+            Screen::Sandbox(sandbox) => sandbox.advance(),
+            _ => false
+        };
+        let playing = matches!(screen, Screen::Playing) || stepping;
+        // Physics keeps running after game over so the top out can finish falling apart
+        let simulating = playing || matches!(screen, Screen::GameOver(_) | Screen::PuzzleEnd(_));
+        let (game_tick, physics_ticks) = debug.as_mut().map_or((true, PHYSICS_TICKS_PER_FRAME), DebugControls::tick);
+        if playing && game_tick {
+            game.game_update(game_frame);
+        }
         #[cfg(log)]
         log_elapsed_time(frame_count, PROFILING_LOG_RATE, "Game Logic Update", profile_timer);
 
         #[cfg(log)]
         reset_timer(frame_count, PROFILING_LOG_RATE, &mut profile_timer);
-        game.physics_update();
-        game.physics_update();
+        if simulating {
+            for _ in 0..physics_ticks {
+                game.physics_update();
+            }
+        }
         #[cfg(log)]
         log_elapsed_time(frame_count, PROFILING_LOG_RATE, "Physics Update", profile_timer);
 
+        if simulating {
+            for event in game.take_events() {
+                audio.handle_event(&event);
+                if playing && matches!(event, GameEvent::GameOver | GameEvent::Won) && game.puzzle().is_some() {
+                    let solved = matches!(event, GameEvent::Won);
+                    let has_next = game.puzzle().and_then(next_puzzle).is_some();
+                    screen = Screen::PuzzleEnd(PuzzleEnd::new(solved, has_next));
+                }
+                else if playing && matches!(event, GameEvent::GameOver | GameEvent::Won) {
+                    let score = HighScore {
+                        name: String::new(),
+                        score: game.score(),
+                        level: game.level(),
+                        clears: game.clears(),
+                        duration: game.elapsed(),
+                        timestamp: now_timestamp(),
+                        seed: game.seed()
+                    };
+                    screen = Screen::GameOver(GameOverScreen::new(game.mode(), score, matches!(event, GameEvent::Won), &high_scores));
+                }
+            }
+            audio.set_danger(game.danger());
+        }
+        // Effects and the frame count only move on with game ticks, so they stay in step while debugging
+        if simulating && game_tick {
+            game.update_effects();
+            game_frame += 1;
+        }
+
         #[cfg(log)]
         reset_timer(frame_count, PROFILING_LOG_RATE, &mut profile_timer);
         game.draw(&mut canvas);
+        let (width, height) = display.logical_size();
+        let screen_rect = Rect::new(0, 0, width, height);
+        match &mut screen {
+            Screen::Title(title) => title.draw(&mut canvas, &font, screen_rect, &game, &audio, &high_scores),
+            Screen::Playing => hud::draw(&mut canvas, &font, &game),
+            Screen::Paused(pause_menu) => {
+                hud::draw(&mut canvas, &font, &game);
+                pause_menu.draw(&mut canvas, &font, screen_rect, &game, &audio);
+            }
+            Screen::GameOver(game_over) => {
+                hud::draw(&mut canvas, &font, &game);
+                game_over.draw(&mut canvas, &font, screen_rect, &high_scores);
+            }
+            Screen::PuzzleSelect(puzzle_select) => puzzle_select.draw(&mut canvas, &font, screen_rect),
+            Screen::PuzzleEnd(puzzle_end) => {
+                hud::draw(&mut canvas, &font, &game);
+                puzzle_end.draw(&mut canvas, &font, screen_rect);
+            }
+            Screen::Sandbox(sandbox) => {
+                hud::draw(&mut canvas, &font, &game);
+                sandbox.draw(&mut canvas, &font, &game);
+            }
+        }
+        if let Some(debug) = &debug {
+            debug.draw(&mut canvas, &font, &game, height);
+        }
+        if take_screenshot {
+            match capture::screenshot(&canvas) {
+                Ok(path) => col_println!((green, bold), "Saved screenshot to {}", path.display()),
+                Err(e) => col_println!((red, bold), "Failed to save screenshot: {}", e)
+            }
+        }
+        // Drawn after the screenshot so it doesn't show up in it
+        if recorder.is_recording() {
+            recorder.record(&game.board_frame());
+            hud::draw_recording(&mut canvas, &font);
+        }
         #[cfg(log)]
         log_elapsed_time(frame_count, PROFILING_LOG_RATE, "Game Draw", profile_timer);
 
@@ -210,5 +601,9 @@ pub fn main() -> Result<(), String> {
         frame_count = frame_count.wrapping_add(1);
     }
 
+    if recorder.is_recording() {
+        recorder.toggle()?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,170 @@
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use crate::text::Font;
+
+pub const TITLE_SCALE: u32 = 6;
+pub const ITEM_SCALE: u32 = 4;
+const OVERLAY_ALPHA: u8 = 200;
+const ITEM_COLOUR: Color = Color::RGB(150, 150, 150);
+const FOCUS_COLOUR: Color = Color::WHITE;
+const LABEL_COLOUR: Color = Color::RGB(100, 100, 100);
+
+/// Menu navigation, from either the keyboard or a controller
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+impl MenuInput {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::KeyDown { keycode: Some(Keycode::Up), .. } | Event::ControllerButtonDown { button: Button::DPadUp, .. } => Some(MenuInput::Up),
+            Event::KeyDown { keycode: Some(Keycode::Down), .. } | Event::ControllerButtonDown { button: Button::DPadDown, .. } => Some(MenuInput::Down),
+            Event::KeyDown { keycode: Some(Keycode::Left), .. } | Event::ControllerButtonDown { button: Button::DPadLeft, .. } => Some(MenuInput::Left),
+            Event::KeyDown { keycode: Some(Keycode::Right), .. } | Event::ControllerButtonDown { button: Button::DPadRight, .. } => Some(MenuInput::Right),
+            Event::KeyDown { keycode: Some(Keycode::Return | Keycode::Space), .. } | Event::ControllerButtonDown { button: Button::A, .. } => Some(MenuInput::Select),
+            Event::KeyDown { keycode: Some(Keycode::Escape | Keycode::Backspace), .. } | Event::ControllerButtonDown { button: Button::B, .. } => Some(MenuInput::Back),
+            _ => None
+        }
+    }
+}
+
+/// What the focused item should do in response to an input
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MenuAction {
+    Select(usize),
+    /// Left or right on an item, for changing a setting
+    Adjust(usize, i32),
+    Back,
+}
+
+/// Darkens everything already drawn in `area` so a menu can be drawn over it
+pub fn draw_overlay(canvas: &mut Canvas<Window>, area: Rect) {
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, OVERLAY_ALPHA));
+    canvas.fill_rect(area).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+}
+
+/// Draws `text` horizontally centred in `area` with its top at `y`
+pub fn draw_centred(canvas: &mut Canvas<Window>, font: &Font, text: &str, area: Rect, y: i32, scale: u32, colour: Color) {
+    let width = font.text_width(text, scale) as i32;
+    font.draw(canvas, text, (area.center().x() - width / 2, y), scale, colour);
+}
+
+pub enum Widget {
+    Button(String),
+    /// A setting drawn with its current value, changed with left and right
+    Setting(String, String),
+    /// Text that can't be focused
+    Label(String),
+}
+
+impl Widget {
+    fn focusable(&self) -> bool {
+        !matches!(self, Widget::Label(_))
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Widget::Button(label) | Widget::Label(label) => label.clone(),
+            Widget::Setting(label, value) => format!("{}: {}", label, value),
+        }
+    }
+}
+
+/// A vertical list of widgets with one focused at a time
+pub struct Menu {
+    pub title: String,
+    widgets: Vec<Widget>,
+    focus: usize,
+}
+
+impl Menu {
+    pub fn new(title: &str, widgets: Vec<Widget>) -> Self {
+        let mut menu = Self {
+            title: title.to_string(),
+            widgets,
+            focus: 0
+        };
+        menu.focus = menu.widgets.iter().position(Widget::focusable).unwrap_or(0);
+        menu
+    }
+
+    /// A menu of buttons with the given labels
+    pub fn buttons(title: &str, labels: &[&str]) -> Self {
+        Self::new(title, labels.iter().map(|label| Widget::Button(label.to_string())).collect())
+    }
+
+    /// Replaces the value shown by a `Setting`
+    pub fn set_value(&mut self, index: usize, value: String) {
+        if let Widget::Setting(_, current) = &mut self.widgets[index] {
+            *current = value;
+        }
+    }
+
+    fn move_focus(&mut self, step: isize) {
+        let count = self.widgets.len() as isize;
+        let mut focus = self.focus as isize;
+        for _ in 0..count {
+            focus = (focus + step).rem_euclid(count);
+            if self.widgets[focus as usize].focusable() {
+                self.focus = focus as usize;
+                return;
+            }
+        }
+    }
+
+    /// Moves the focus, wrapping at either end and skipping labels, and returns any action for the caller to carry out
+    pub fn handle_input(&mut self, input: MenuInput) -> Option<MenuAction> {
+        if !self.widgets.iter().any(Widget::focusable) {
+            return if input == MenuInput::Back { Some(MenuAction::Back) } else { None };
+        }
+
+        match input {
+            MenuInput::Up => {
+                self.move_focus(-1);
+                None
+            }
+            MenuInput::Down => {
+                self.move_focus(1);
+                None
+            }
+            MenuInput::Left => Some(MenuAction::Adjust(self.focus, -1)),
+            MenuInput::Right => Some(MenuAction::Adjust(self.focus, 1)),
+            MenuInput::Select => Some(MenuAction::Select(self.focus)),
+            MenuInput::Back => Some(MenuAction::Back),
+        }
+    }
+
+    /// Darkens everything already drawn in `area` and draws the menu centred over it
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &Font, area: Rect) {
+        draw_overlay(canvas, area);
+
+        let height = font.line_height(TITLE_SCALE) * 2 + font.line_height(ITEM_SCALE) * self.widgets.len() as u32;
+        let mut y = area.center().y() - height as i32 / 2;
+
+        draw_centred(canvas, font, &self.title, area, y, TITLE_SCALE, FOCUS_COLOUR);
+        y += font.line_height(TITLE_SCALE) as i32 * 2;
+
+        for (i, widget) in self.widgets.iter().enumerate() {
+            let (text, colour) = match widget {
+                _ if i == self.focus && widget.focusable() => (format!("> {} <", widget.text()), FOCUS_COLOUR),
+                Widget::Label(_) => (widget.text(), LABEL_COLOUR),
+                _ => (widget.text(), ITEM_COLOUR)
+            };
+            draw_centred(canvas, font, &text, area, y, ITEM_SCALE, colour);
+            y += font.line_height(ITEM_SCALE) as i32;
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::audio::Audio;
+use crate::game::Game;
+use crate::menu::{Menu, MenuAction, MenuInput};
+use crate::settings::SettingsMenu;
+use crate::text::Font;
+
+const RESUME: usize = 0;
+const RESTART: usize = 1;
+const SETTINGS: usize = 2;
+const SAVE_AND_QUIT: usize = 3;
+const QUIT: usize = 4;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PauseOutcome {
+    Resume,
+    Restart,
+    /// Suspend the game to be continued from the title screen
+    SaveAndQuit,
+    QuitToTitle,
+}
+
+/// Menu shown over the frozen game
+pub struct PauseMenu {
+    menu: Menu,
+    settings: Option<SettingsMenu>,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self {
+            menu: Menu::buttons("Paused", &["Resume", "Restart", "Settings", "Save and quit", "Quit to title"]),
+            settings: None
+        }
+    }
+
+    pub fn handle_input<const W: usize, const H: usize>(&mut self, input: MenuInput, game: &mut Game<W, H>, audio: &mut Audio) -> Option<PauseOutcome> {
+        if let Some(settings) = &mut self.settings {
+            if settings.handle_input(input, game, audio) {
+                self.settings = None;
+            }
+            return None;
+        }
+
+        match self.menu.handle_input(input) {
+            Some(MenuAction::Back) | Some(MenuAction::Select(RESUME)) => Some(PauseOutcome::Resume),
+            Some(MenuAction::Select(RESTART)) => Some(PauseOutcome::Restart),
+            Some(MenuAction::Select(SETTINGS)) => {
+                self.settings = Some(SettingsMenu::new());
+                None
+            }
+            Some(MenuAction::Select(SAVE_AND_QUIT)) => Some(PauseOutcome::SaveAndQuit),
+            Some(MenuAction::Select(QUIT)) => Some(PauseOutcome::QuitToTitle),
+            _ => None
+        }
+    }
+
+    pub fn draw<const W: usize, const H: usize>(&mut self, canvas: &mut Canvas<Window>, font: &Font, area: Rect, game: &Game<W, H>, audio: &Audio) {
+        match &mut self.settings {
+            Some(settings) => settings.draw(canvas, font, area, game, audio),
+            None => self.menu.draw(canvas, font, area)
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::game::puzzle::{Puzzle, PUZZLE_DIR};
+use crate::menu::{Menu, MenuAction, MenuInput, Widget};
+use crate::text::Font;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PuzzleSelectOutcome {
+    /// Play the puzzle at this index of `PuzzleSelect::puzzles`
+    Start(usize),
+    Back,
+}
+
+/// Lists the puzzles found in the puzzle directory
+pub struct PuzzleSelect {
+    puzzles: Vec<Puzzle>,
+    menu: Menu,
+}
+
+impl PuzzleSelect {
+    pub fn new() -> Self {
+        let puzzles = Puzzle::load_all(Path::new(PUZZLE_DIR));
+        let mut widgets: Vec<Widget> = puzzles.iter().enumerate()
+            .map(|(i, puzzle)| Widget::Setting(format!("{}. {}", i + 1, puzzle.name), puzzle.goal.description()))
+            .collect();
+        if puzzles.is_empty() {
+            widgets.push(Widget::Label("No puzzles found".to_string()));
+        }
+        widgets.push(Widget::Button("Back".to_string()));
+
+        Self {
+            puzzles,
+            menu: Menu::new("Select puzzle", widgets)
+        }
+    }
+
+    pub fn puzzles(&self) -> &[Puzzle] {
+        &self.puzzles
+    }
+
+    pub fn handle_input(&mut self, input: MenuInput) -> Option<PuzzleSelectOutcome> {
+        match self.menu.handle_input(input) {
+            Some(MenuAction::Select(index)) if index < self.puzzles.len() => Some(PuzzleSelectOutcome::Start(index)),
+            Some(MenuAction::Select(_)) | Some(MenuAction::Back) => Some(PuzzleSelectOutcome::Back),
+            _ => None
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &Font, area: Rect) {
+        self.menu.draw(canvas, font, area);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PuzzleEndOutcome {
+    Next,
+    Retry,
+    LevelSelect,
+    QuitToTitle,
+}
+
+/// Shown when a puzzle is solved or failed
+pub struct PuzzleEnd {
+    items: Vec<PuzzleEndOutcome>,
+    menu: Menu,
+}
+
+impl PuzzleEnd {
+    /// `has_next` offers to go straight on to the next puzzle after solving one
+    pub fn new(solved: bool, has_next: bool) -> Self {
+        let mut items = vec![PuzzleEndOutcome::Retry, PuzzleEndOutcome::LevelSelect, PuzzleEndOutcome::QuitToTitle];
+        if solved && has_next {
+            items.insert(0, PuzzleEndOutcome::Next);
+        }
+
+        let labels: Vec<&str> = items.iter().map(|item| match item {
+            PuzzleEndOutcome::Next => "Next puzzle",
+            PuzzleEndOutcome::Retry => "Retry",
+            PuzzleEndOutcome::LevelSelect => "Level select",
+            PuzzleEndOutcome::QuitToTitle => "Quit to title",
+        }).collect();
+
+        Self {
+            menu: Menu::buttons(if solved { "Solved!" } else { "Failed" }, &labels),
+            items
+        }
+    }
+
+    pub fn handle_input(&mut self, input: MenuInput) -> Option<PuzzleEndOutcome> {
+        match self.menu.handle_input(input) {
+            Some(MenuAction::Select(index)) => Some(self.items[index]),
+            Some(MenuAction::Back) => Some(PuzzleEndOutcome::LevelSelect),
+            _ => None
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &Font, area: Rect) {
+        self.menu.draw(canvas, font, area);
+    }
+}
+
+/// The puzzle after `current` in the puzzle directory, if there is one
+pub fn next_puzzle(current: &Puzzle) -> Option<Puzzle> {
+    let mut puzzles = Puzzle::load_all(Path::new(PUZZLE_DIR));
+    let index = puzzles.iter().position(|puzzle| puzzle.path == current.path)?;
+    if index + 1 < puzzles.len() { Some(puzzles.swap_remove(index + 1)) } else { None }
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::col_println;
+use crate::config::data_dir;
+use crate::display::Display;
+use crate::game::{ColourCode, Game};
+use crate::game::palette::MAX_COLOURS;
+use crate::game::tetromino::TetrominoShape;
+use crate::highscores::now_timestamp;
+use crate::text::Font;
+
+const PANEL_MARGIN: i32 = 20;
+const PANEL_SCALE: u32 = 2;
+const LABEL_COLOUR: Color = Color::RGB(150, 150, 150);
+const MAX_BRUSH_RADIUS: usize = 40;
+/// Number keys pick a colour, in palette order
+const COLOUR_KEYS: [Keycode; MAX_COLOURS] = [
+    Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4, Keycode::Num5, Keycode::Num6, Keycode::Num7, Keycode::Num8,
+];
+
+const HELP: [&str; 10] = [
+    "1-8 colour",
+    "B brush  E eraser",
+    "F fill  T piece",
+    "[ ] brush size",
+    "Right click erases",
+    "Space pause",
+    "N step",
+    "S save board",
+    "C clear",
+    "Esc quit",
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Tool {
+    Brush,
+    Eraser,
+    Fill,
+    /// Drops the selected shape where clicked
+    Piece,
+}
+
+impl Tool {
+    fn name(&self) -> &'static str {
+        match self {
+            Tool::Brush => "Brush",
+            Tool::Eraser => "Eraser",
+            Tool::Fill => "Fill",
+            Tool::Piece => "Piece",
+        }
+    }
+}
+
+/// Paint tools and physics controls for sandbox mode
+pub struct Sandbox {
+    tool: Tool,
+    colour: ColourCode,
+    brush_radius: usize,
+    shape: usize,
+    paused: bool,
+    /// Run one frame of the simulation while paused
+    step: bool,
+    /// Colour being painted by the held mouse button, or `None` to erase, and the last cell painted
+    stroke: Option<(Option<ColourCode>, (usize, usize))>,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Self {
+            tool: Tool::Brush,
+            colour: ColourCode(0),
+            brush_radius: 4,
+            shape: 0,
+            paused: false,
+            step: false,
+            stroke: None
+        }
+    }
+
+    /// Whether the simulation should run this frame
+    pub fn advance(&mut self) -> bool {
+        let step = self.step;
+        self.step = false;
+        !self.paused || step
+    }
+
+    /// Returns true when the player leaves the sandbox
+    pub fn handle_event<const W: usize, const H: usize>(&mut self, event: &Event, game: &mut Game<W, H>, display: &Display, frame_count: u64) -> bool {
+        match event {
+            Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return true,
+            Event::KeyDown { keycode: Some(keycode), .. } => self.handle_key(*keycode, game, frame_count),
+            Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                let cell = match game.cell_at(display.to_logical((*x, *y))) {
+                    Some(cell) => cell,
+                    None => return false
+                };
+                let code = if *mouse_btn == MouseButton::Right { None } else { Some(self.colour) };
+
+                match (self.tool, mouse_btn) {
+                    (Tool::Piece, MouseButton::Left) => {
+                        game.drop_piece(TetrominoShape::ALL[self.shape], self.colour, cell, frame_count);
+                    }
+                    (Tool::Fill, _) => game.fill(cell, code),
+                    (Tool::Eraser, _) => self.start_stroke(game, None, cell),
+                    _ => self.start_stroke(game, code, cell)
+                }
+            }
+            Event::MouseMotion { x, y, .. } => {
+                let (code, last) = match self.stroke {
+                    Some(stroke) => stroke,
+                    None => return false
+                };
+                if let Some(cell) = game.cell_at(display.to_logical((*x, *y))) {
+                    game.paint_line(last, cell, self.brush_radius, code);
+                    self.stroke = Some((code, cell));
+                }
+            }
+            Event::MouseButtonUp { .. } => self.stroke = None,
+            _ => {}
+        }
+        false
+    }
+
+    fn start_stroke<const W: usize, const H: usize>(&mut self, game: &mut Game<W, H>, code: Option<ColourCode>, cell: (usize, usize)) {
+        game.paint(cell, self.brush_radius, code);
+        self.stroke = Some((code, cell));
+    }
+
+    fn handle_key<const W: usize, const H: usize>(&mut self, keycode: Keycode, game: &mut Game<W, H>, frame_count: u64) {
+        match keycode {
+            Keycode::B => self.tool = Tool::Brush,
+            Keycode::E => self.tool = Tool::Eraser,
+            Keycode::F => self.tool = Tool::Fill,
+            // Pressing it again picks the next shape
+            Keycode::T if self.tool == Tool::Piece => self.shape = (self.shape + 1) % TetrominoShape::ALL.len(),
+            Keycode::T => self.tool = Tool::Piece,
+            Keycode::LeftBracket => self.brush_radius = self.brush_radius.saturating_sub(1),
+            Keycode::RightBracket => self.brush_radius = (self.brush_radius + 1).min(MAX_BRUSH_RADIUS),
+            Keycode::Space => self.paused = !self.paused,
+            Keycode::N if self.paused => self.step = true,
+            Keycode::C => game.reset(frame_count),
+            Keycode::S => self.save(game),
+            Keycode::Left => game.move_left(),
+            Keycode::Right => game.move_right(),
+            Keycode::Down => game.move_down(),
+            Keycode::Up => game.move_down_amount(10_000),
+            Keycode::R => game.rotate(),
+            _ => if let Some(index) = COLOUR_KEYS.iter().position(|key| *key == keycode) {
+                self.colour = ColourCode(index as u8);
+            }
+        }
+    }
+
+    /// Saves the board as a PNG, which can be used as a puzzle's starting board
+    fn save<const W: usize, const H: usize>(&self, game: &Game<W, H>) {
+        let directory = match data_dir() {
+            Some(directory) => directory.join("sandboxes"),
+            None => {
+                col_println!((red, bold), "Failed to save sandbox: No data directory");
+                return;
+            }
+        };
+        let path = directory.join(format!("sandbox-{}.png", now_timestamp()));
+        match fs::create_dir_all(&directory).map_err(|e| e.to_string()).and_then(|_| game.export_puzzle_board(&path)) {
+            Ok(()) => col_println!((green, bold), "Saved sandbox to {}", path.display()),
+            Err(e) => col_println!((red, bold), "Failed to save sandbox: {}", e)
+        }
+    }
+
+    /// Draws the selected tool and the controls to the left of the board
+    pub fn draw<const W: usize, const H: usize>(&self, canvas: &mut Canvas<Window>, font: &Font, game: &Game<W, H>) {
+        let line = font.line_height(PANEL_SCALE) as i32;
+        let x = PANEL_MARGIN;
+        let mut y = game.board_rect().top();
+
+        let tool = match self.tool {
+            Tool::Piece => format!("{} {}", self.tool.name(), TetrominoShape::ALL[self.shape].name()),
+            Tool::Brush | Tool::Eraser => format!("{} {}", self.tool.name(), self.brush_radius),
+            Tool::Fill => self.tool.name().to_string(),
+        };
+        font.draw(canvas, &tool, (x, y), PANEL_SCALE, Color::WHITE);
+        y += line;
+
+        let swatch_size = font.line_height(PANEL_SCALE) - PANEL_SCALE * 2;
+        canvas.set_draw_color(game.palette().colour(self.colour.index()));
+        canvas.fill_rect(Rect::new(x, y, swatch_size, swatch_size)).unwrap();
+        font.draw(canvas, &format!("Colour {}", self.colour.index() + 1), (x + swatch_size as i32 + PANEL_MARGIN / 2, y), PANEL_SCALE, Color::WHITE);
+        y += line;
+
+        let state = if self.paused { "Paused" } else { "Running" };
+        font.draw(canvas, state, (x, y), PANEL_SCALE, if self.paused { Color::RED } else { Color::WHITE });
+        y += line * 2;
+
+        for help in HELP {
+            font.draw(canvas, help, (x, y), PANEL_SCALE, LABEL_COLOUR);
+            y += line;
+        }
+    }
+}
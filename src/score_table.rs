@@ -0,0 +1,81 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::game::mode::GameMode;
+use crate::highscores::HighScores;
+use crate::hud::format_duration;
+use crate::menu::{draw_centred, draw_overlay, MenuInput, ITEM_SCALE, TITLE_SCALE};
+use crate::text::Font;
+
+const TABLE_SCALE: u32 = 2;
+const HEADER_COLOUR: Color = Color::RGB(150, 150, 150);
+const HIGHLIGHT_COLOUR: Color = Color::RGB(255, 220, 0);
+
+/// Viewer for the high score table of one mode at a time, switched with left and right
+pub struct ScoreTable {
+    modes: Vec<GameMode>,
+    mode: usize,
+    /// Rank of a score just set, drawn in a different colour
+    highlight: Option<usize>,
+}
+
+impl ScoreTable {
+    pub fn new(mode: GameMode, highlight: Option<usize>) -> Self {
+        let modes: Vec<GameMode> = GameMode::with_high_scores().collect();
+        Self {
+            mode: modes.iter().position(|m| *m == mode).unwrap_or(0),
+            modes,
+            highlight
+        }
+    }
+
+    /// Returns true once the player closes the table
+    pub fn handle_input(&mut self, input: MenuInput) -> bool {
+        let count = self.modes.len();
+        match input {
+            MenuInput::Left => {
+                self.mode = (self.mode + count - 1) % count;
+                self.highlight = None;
+            }
+            MenuInput::Right => {
+                self.mode = (self.mode + 1) % count;
+                self.highlight = None;
+            }
+            MenuInput::Select | MenuInput::Back => return true,
+            _ => {}
+        }
+        false
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, font: &Font, area: Rect, high_scores: &HighScores) {
+        draw_overlay(canvas, area);
+
+        let mode = self.modes[self.mode];
+        let mut y = area.top() + font.line_height(TITLE_SCALE) as i32 * 2;
+        draw_centred(canvas, font, "High scores", area, y, TITLE_SCALE, Color::WHITE);
+        y += font.line_height(TITLE_SCALE) as i32 * 2;
+        draw_centred(canvas, font, &format!("< {} >", mode.name()), area, y, ITEM_SCALE, Color::WHITE);
+        y += font.line_height(ITEM_SCALE) as i32 * 2;
+
+        let scores = high_scores.scores(mode);
+        if scores.is_empty() {
+            draw_centred(canvas, font, "No high scores yet", area, y, ITEM_SCALE, HEADER_COLOUR);
+            return;
+        }
+
+        let header = format!("{:>2} {:<10} {:>9} {:>3} {:>4} {:>9} {:>10}", "#", "Name", "Score", "Lvl", "Clr", "Time", "Date");
+        draw_centred(canvas, font, &header, area, y, TABLE_SCALE, HEADER_COLOUR);
+        y += font.line_height(TABLE_SCALE) as i32 * 2;
+
+        for (rank, score) in scores.iter().enumerate() {
+            let row = format!(
+                "{:>2} {:<10} {:>9} {:>3} {:>4} {:>9} {:>10}",
+                rank + 1, score.name, score.score, score.level, score.clears, format_duration(score.duration), score.date()
+            );
+            let colour = if self.highlight == Some(rank) { HIGHLIGHT_COLOUR } else { Color::WHITE };
+            draw_centred(canvas, font, &row, area, y, TABLE_SCALE, colour);
+            y += font.line_height(TABLE_SCALE) as i32;
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::audio::Audio;
+use crate::game::{Game, MIN_COLOURS};
+use crate::game::palette::MAX_COLOURS;
+use crate::menu::{Menu, MenuAction, MenuInput, Widget};
+use crate::text::Font;
+
+const PALETTE: usize = 0;
+const PATTERNS: usize = 1;
+const COLOURS: usize = 2;
+const SCREEN_SHAKE: usize = 3;
+const ASSIST: usize = 4;
+const VOLUME: usize = 5;
+const MUTE: usize = 6;
+const BACK: usize = 7;
+
+fn on_off(enabled: bool) -> String {
+    if enabled { "On".to_string() } else { "Off".to_string() }
+}
+
+fn setting(label: &str) -> Widget {
+    Widget::Setting(label.to_string(), String::new())
+}
+
+/// Settings page shared by the title screen and pause menu, changing the game and audio directly
+pub struct SettingsMenu {
+    menu: Menu,
+}
+
+impl SettingsMenu {
+    pub fn new() -> Self {
+        Self {
+            menu: Menu::new("Settings", vec![
+                setting("Palette"), setting("Patterns"), setting("Colours"), setting("Screen shake"),
+                setting("Assist"), setting("Volume"), setting("Mute"), Widget::Button("Back".to_string())
+            ])
+        }
+    }
+
+    /// Returns true once the player leaves the page
+    pub fn handle_input<const W: usize, const H: usize>(&mut self, input: MenuInput, game: &mut Game<W, H>, audio: &mut Audio) -> bool {
+        match self.menu.handle_input(input) {
+            Some(MenuAction::Back) | Some(MenuAction::Select(BACK)) => return true,
+            Some(MenuAction::Select(item)) => Self::change_setting(item, 1, game, audio),
+            Some(MenuAction::Adjust(item, step)) => Self::change_setting(item, step, game, audio),
+            None => {}
+        }
+        false
+    }
+
+    fn change_setting<const W: usize, const H: usize>(item: usize, step: i32, game: &mut Game<W, H>, audio: &mut Audio) {
+        match item {
+            PALETTE => game.set_palette(game.palette().cycle_built_in(step)),
+            PATTERNS => {
+                let mut palette = game.palette().clone();
+                palette.patterns = !palette.patterns;
+                game.set_palette(palette);
+            }
+            COLOURS => {
+                let colours = (game.colour_count() as i32 + step).clamp(MIN_COLOURS as i32, MAX_COLOURS as i32);
+                game.set_colour_count(colours as usize);
+            }
+            SCREEN_SHAKE => game.set_screen_shake(!game.screen_shake()),
+            ASSIST => game.set_assist(!game.assist_enabled()),
+            VOLUME if step < 0 => audio.volume_down(),
+            VOLUME => audio.volume_up(),
+            MUTE => audio.toggle_mute(),
+            _ => {}
+        }
+    }
+
+    pub fn draw<const W: usize, const H: usize>(&mut self, canvas: &mut Canvas<Window>, font: &Font, area: Rect, game: &Game<W, H>, audio: &Audio) {
+        self.menu.set_value(PALETTE, game.palette().name.clone());
+        self.menu.set_value(PATTERNS, on_off(game.palette().patterns));
+        self.menu.set_value(COLOURS, game.colour_count().to_string());
+        self.menu.set_value(SCREEN_SHAKE, on_off(game.screen_shake()));
+        self.menu.set_value(ASSIST, on_off(game.assist_enabled()));
+        self.menu.set_value(VOLUME, format!("{}%", audio.volume_percent()));
+        self.menu.set_value(MUTE, on_off(audio.muted()));
+        self.menu.draw(canvas, font, area);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fs;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::col_println;
+
+const BUILTIN_FONT: &str = include_str!("../static/font.txt");
+
+pub struct Font {
+    glyph_size: (u32, u32),
+    glyphs: HashMap<char, Vec<Vec<bool>>>,
+}
+
+impl Font {
+    /// Loads a bitmap font, falling back to the built-in font if the file is missing or invalid
+    pub fn load(path: &str) -> Self {
+        match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|source| Self::parse(&source)) {
+            Ok(font) => font,
+            Err(e) => {
+                col_println!((yellow, bold), "Failed to load font '{}' ({}), using built-in font", path, e);
+                Self::builtin()
+            }
+        }
+    }
+
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_FONT).unwrap()
+    }
+
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut lines = source.lines();
+        let mut glyph_size = None;
+        let mut glyphs = HashMap::new();
+
+        while let Some(line) = lines.next() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with("# ") { continue; }
+
+            if let Some(size) = line.strip_prefix("size ") {
+                let mut parts = size.split_whitespace().map(|p| p.parse::<u32>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(width)), Some(Ok(height))) if width > 0 && height > 0 => glyph_size = Some((width, height)),
+                    _ => return Err(format!("Invalid size '{}'", line))
+                }
+            }
+            else if let Some(name) = line.strip_prefix("char ") {
+                let (width, height) = glyph_size.ok_or("Glyph defined before size")?;
+
+                let mut chars = name.chars();
+                let c = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(format!("Invalid glyph name '{}'", name))
+                };
+
+                let mut rows = Vec::with_capacity(height as usize);
+                for _ in 0..height {
+                    let row = lines.next().ok_or(format!("Glyph '{}' ended early", c))?;
+                    let row: Vec<bool> = row.trim_end().chars().map(|p| p == '#').collect();
+                    if row.len() != width as usize {
+                        return Err(format!("Glyph '{}' has a row of the wrong width", c));
+                    }
+                    rows.push(row);
+                }
+                glyphs.insert(c, rows);
+            }
+            else {
+                return Err(format!("Unexpected line '{}'", line));
+            }
+        }
+
+        Ok(Self {
+            glyph_size: glyph_size.ok_or("Missing size")?,
+            glyphs
+        })
+    }
+
+    pub fn text_width(&self, text: &str, scale: u32) -> u32 {
+        let count = text.chars().count() as u32;
+        if count == 0 { return 0; }
+        (count * (self.glyph_size.0 + 1) - 1) * scale
+    }
+
+    pub fn line_height(&self, scale: u32) -> u32 {
+        (self.glyph_size.1 + 2) * scale
+    }
+
+    /// Draws `text` with its top left corner at `position`. Characters without a glyph are left blank
+    pub fn draw(&self, canvas: &mut Canvas<Window>, text: &str, position: (i32, i32), scale: u32, colour: Color) {
+        let mut rects = Vec::new();
+
+        for (i, c) in text.chars().enumerate() {
+            let glyph = match self.glyphs.get(&c.to_ascii_uppercase()) {
+                Some(glyph) => glyph,
+                None => continue
+            };
+            let glyph_x = position.0 + (i as u32 * (self.glyph_size.0 + 1) * scale) as i32;
+
+            for (y, row) in glyph.iter().enumerate() {
+                for (x, lit) in row.iter().enumerate() {
+                    if *lit {
+                        rects.push(Rect::new(
+                            glyph_x + (x as u32 * scale) as i32, position.1 + (y as u32 * scale) as i32,
+                            scale, scale
+                        ));
+                    }
+                }
+            }
+        }
+
+        if rects.is_empty() { return; }
+        canvas.set_draw_color(colour);
+        canvas.fill_rects(&rects).unwrap();
+    }
+}
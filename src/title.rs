@@ -0,0 +1,113 @@
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use crate::audio::Audio;
+use crate::game::Game;
+use crate::game::mode::GameMode;
+use crate::highscores::HighScores;
+use crate::menu::{Menu, MenuAction, MenuInput, Widget};
+use crate::score_table::ScoreTable;
+use crate::settings::SettingsMenu;
+use crate::text::Font;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum MainItem {
+    Continue,
+    Play,
+    Options,
+    HighScores,
+    Quit,
+}
+
+impl MainItem {
+    fn label(&self) -> &'static str {
+        match self {
+            MainItem::Continue => "Continue",
+            MainItem::Play => "Play",
+            MainItem::Options => "Options",
+            MainItem::HighScores => "High scores",
+            MainItem::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TitleOutcome {
+    Start(GameMode),
+    /// Resume the suspended game
+    Continue,
+    Quit,
+}
+
+enum Page {
+    Main,
+    Modes,
+    Settings(SettingsMenu),
+    HighScores(ScoreTable),
+}
+
+/// The first screen, leading to mode select, options and high scores
+pub struct TitleScreen {
+    page: Page,
+    main_items: Vec<MainItem>,
+    main: Menu,
+    modes: Menu,
+}
+
+impl TitleScreen {
+    /// `can_continue` adds an option to resume a suspended game
+    pub fn new(can_continue: bool) -> Self {
+        let mut main_items = vec![MainItem::Play, MainItem::Options, MainItem::HighScores, MainItem::Quit];
+        if can_continue {
+            main_items.insert(0, MainItem::Continue);
+        }
+        let mut modes: Vec<Widget> = GameMode::ALL.iter().map(|mode| Widget::Button(mode.name().to_string())).collect();
+        modes.push(Widget::Button("Back".to_string()));
+
+        Self {
+            page: Page::Main,
+            main: Menu::new("Physics Tetris", main_items.iter().map(|item| Widget::Button(item.label().to_string())).collect()),
+            main_items,
+            modes: Menu::new("Select mode", modes)
+        }
+    }
+
+    pub fn handle_input<const W: usize, const H: usize>(&mut self, input: MenuInput, game: &mut Game<W, H>, audio: &mut Audio) -> Option<TitleOutcome> {
+        match &mut self.page {
+            Page::Main => if let Some(MenuAction::Select(index)) = self.main.handle_input(input) {
+                match self.main_items[index] {
+                    MainItem::Continue => return Some(TitleOutcome::Continue),
+                    MainItem::Play => self.page = Page::Modes,
+                    MainItem::Options => self.page = Page::Settings(SettingsMenu::new()),
+                    MainItem::HighScores => self.page = Page::HighScores(ScoreTable::new(GameMode::Endless, None)),
+                    MainItem::Quit => return Some(TitleOutcome::Quit),
+                }
+            },
+            Page::Modes => match self.modes.handle_input(input) {
+                Some(MenuAction::Select(index)) if index < GameMode::ALL.len() => return Some(TitleOutcome::Start(GameMode::ALL[index])),
+                Some(MenuAction::Select(_)) | Some(MenuAction::Back) => self.page = Page::Main,
+                _ => {}
+            },
+            Page::Settings(settings) => {
+                if settings.handle_input(input, game, audio) {
+                    self.page = Page::Main;
+                }
+            }
+            Page::HighScores(table) => {
+                if table.handle_input(input) {
+                    self.page = Page::Main;
+                }
+            }
+        }
+        None
+    }
+
+    pub fn draw<const W: usize, const H: usize>(&mut self, canvas: &mut Canvas<Window>, font: &Font, area: Rect, game: &Game<W, H>, audio: &Audio, high_scores: &HighScores) {
+        match &mut self.page {
+            Page::Main => self.main.draw(canvas, font, area),
+            Page::Modes => self.modes.draw(canvas, font, area),
+            Page::Settings(settings) => settings.draw(canvas, font, area, game, audio),
+            Page::HighScores(table) => table.draw(canvas, font, area, high_scores),
+        }
+    }
+}
@@ -52,4 +52,18 @@ macro_rules! col_print {
             print!("{}", format!($($arg)*)$(.$col_args())*)
         }
     };
-}
\ No newline at end of file
+}
+
+/// Writes to a temporary file next to `path` first and renames it over the old one, so a crash never leaves half a file
+pub fn write_atomically(path: &std::path::Path, bytes: &[u8]) -> Result<(), String> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, bytes).map_err(|e| e.to_string())?;
+    std::fs::rename(&temporary, path).map_err(|e| e.to_string())
+}
+
+/// A path in the system temporary directory unique to this test run, for tests that need real files
+#[cfg(test)]
+pub fn temporary_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("physics_tetris-{}-{}", std::process::id(), name))
+}